@@ -4,6 +4,6 @@ fn main() {
     println!("hello");
     lalrpop::Configuration::new()
         .always_use_colors()
-        .process_file(&"src/lib/parser.lalrpop")
+        .process_file("src/lib/parser.lalrpop")
         .unwrap();
 }
@@ -1,7 +1,11 @@
 use structopt::StructOpt;
 
+use fumola::ast::step::{PutPolicy, StorePolicy};
+use fumola::ast::Sym;
+use fumola::schedule::Policy;
 use log::info;
-use std::io;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 use structopt::{clap, clap::Shell};
 
 pub type OurResult<X> = Result<X, OurError>;
@@ -38,14 +42,323 @@ pub enum CliCommand {
         name = "completions",
         about = "Generate shell scripts for auto-completions."
     )]
-    Completions {
-        shell: Shell,
-    },
+    Completions { shell: Shell },
     Check {
         input: String,
+        /// Write an HTML trace explorer for the final system to this file.
+        #[structopt(long = "html")]
+        html: Option<PathBuf>,
+        /// Write a Chrome trace-event timeline of the run to this file.
+        #[structopt(long = "chrome")]
+        chrome: Option<PathBuf>,
+        #[structopt(flatten)]
+        sched: SchedOpt,
+        /// Report unordered, conflicting store accesses.
+        #[structopt(long = "races")]
+        races: bool,
+        #[structopt(flatten)]
+        fuel: FuelOpt,
+        /// Type check the program first, failing on type errors (inference
+        /// has no recursive types, so it rejects some programs that run).
+        #[structopt(long = "types")]
+        types: bool,
+    },
+    #[structopt(
+        name = "run",
+        about = "Run a program from a file (or - for stdin), exiting with its outcome: 0 all halted, 3 some failed, 4 some waiting, 5 out of fuel."
+    )]
+    Run {
+        /// Program file, or - for stdin.
+        file: PathBuf,
+        /// What to print: system, store, html or chrome.
+        #[structopt(long = "format", default_value = "system")]
+        format: Format,
+        /// Stream step events to this file, as JSON lines.
+        #[structopt(long = "events")]
+        events: Option<PathBuf>,
+        /// Profile the run, printing the profile to stderr.
+        #[structopt(long = "profile")]
+        profile: bool,
+        /// Write the profile as folded stacks (for flamegraph tools) to this file.
+        #[structopt(long = "folded")]
+        folded: Option<PathBuf>,
+        /// Record expression coverage in this file, adding to any coverage it holds.
+        #[structopt(long = "coverage")]
+        coverage: Option<PathBuf>,
+        #[structopt(flatten)]
+        sched: SchedOpt,
+        #[structopt(flatten)]
+        fuel: FuelOpt,
+        /// Type check the program first, failing on type errors (inference
+        /// has no recursive types, so it rejects some programs that run).
+        #[structopt(long = "types")]
+        types: bool,
+        #[structopt(flatten)]
+        store: StoreOpt,
+        /// Print every version of each store symbol, with the process and
+        /// step that put it, after the final system.
+        #[structopt(long = "store-history")]
+        store_history: bool,
+        /// Memoize named nests, replaying a nest that runs again with the
+        /// same free variables and store reads; print the memo table to stderr.
+        #[structopt(long = "memo")]
+        memo: bool,
+    },
+    #[structopt(
+        name = "explore",
+        about = "Explore all process interleavings, reporting final systems and failures."
+    )]
+    Explore {
+        input: String,
+        /// Stop after this many distinct states.
+        #[structopt(long = "max-states", default_value = "100000")]
+        max_states: usize,
+        /// Disable partial-order reduction.
+        #[structopt(long = "no-reduce")]
+        no_reduce: bool,
+    },
+    #[structopt(
+        name = "coverage",
+        about = "Report expression coverage of a program, merged from coverage files of its runs."
+    )]
+    Coverage {
+        /// Program file.
+        file: PathBuf,
+        /// Coverage files, written by `run --coverage`.
+        coverage: Vec<PathBuf>,
+    },
+    #[structopt(
+        name = "effects",
+        about = "List the store symbols a program may put, get and link to, and the processes it may spawn."
+    )]
+    Effects {
+        /// Program file.
+        file: PathBuf,
+    },
+    #[structopt(
+        name = "names",
+        about = "Check that the puts and spawns of a program use distinct names."
+    )]
+    Names {
+        /// Program file.
+        file: PathBuf,
+    },
+    #[structopt(
+        name = "lint",
+        about = "Flag likely mistakes in a program, failing if any is an error."
+    )]
+    Lint {
+        /// Program file.
+        file: PathBuf,
+        /// Skip this lint (unused-let, unused-box, shadowed, unreachable-case,
+        /// duplicate-label, get-never-put or literal-assert).
+        #[structopt(long = "allow")]
+        allow: Vec<fumola::lint::Lint>,
+        /// Skip a lint at one line only, as lint@line (for example,
+        /// get-never-put@8 for a get of a symbol the host puts).
+        #[structopt(long = "allow-at")]
+        allow_at: Vec<fumola::lint::AllowAt>,
+    },
+    #[structopt(
+        name = "debug",
+        about = "Step the processes of a program interactively."
+    )]
+    Debug {
+        /// Program file.
+        file: std::path::PathBuf,
+    },
+    #[structopt(
+        name = "lsp",
+        about = "Serve the Language Server Protocol over stdin and stdout."
+    )]
+    Lsp,
+    #[structopt(
+        name = "repl",
+        about = "Evaluate lines interactively, keeping one system across them."
+    )]
+    Repl {
+        /// Most steps per line.
+        #[structopt(long = "max-steps", default_value = "100000")]
+        max_steps: usize,
     },
 }
 
+#[derive(StructOpt, Debug, Clone)]
+pub struct StoreOpt {
+    /// What puts do to symbols already in the store:
+    /// overwrite, write-once or overwrite-if-equal.
+    #[structopt(long = "store-policy", default_value = "overwrite")]
+    store_policy: PutPolicy,
+    /// Store policy of the puts within a nest, as `path=policy`
+    /// (the path of nest `m` within nest `n` is `n/m`).
+    #[structopt(long = "nest-policy")]
+    nest_policy: Vec<NestPolicyOpt>,
+}
+
+impl StoreOpt {
+    fn policy(self) -> StorePolicy {
+        StorePolicy {
+            default: self.store_policy,
+            nests: self.nest_policy.into_iter().map(|p| (p.0, p.1)).collect(),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct SchedOpt {
+    /// Process schedule: name, round-robin, priority or random.
+    #[structopt(long = "schedule", default_value = "name")]
+    schedule: Policy,
+    /// Seed for the random schedule.
+    #[structopt(long = "seed", default_value = "0")]
+    seed: u64,
+    /// Process priority for the priority schedule, as `name=n`.
+    #[structopt(long = "priority")]
+    priority: Vec<PriorityOpt>,
+}
+
+impl SchedOpt {
+    fn scheduler(self) -> Box<dyn fumola::schedule::Scheduler> {
+        let priorities = self.priority.into_iter().map(|p| (p.0, p.1)).collect();
+        self.schedule.scheduler(self.seed, priorities)
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct FuelOpt {
+    /// Stop after this many global system steps.
+    #[structopt(long = "max-steps")]
+    max_steps: Option<usize>,
+    /// Stop each process after this many of its own steps.
+    #[structopt(long = "max-proc-steps")]
+    max_proc_steps: Option<usize>,
+    /// Stop stepping after this many milliseconds.
+    #[structopt(long = "timeout-ms")]
+    timeout_ms: Option<u64>,
+}
+
+impl FuelOpt {
+    fn fuel(&self) -> fumola::step::Fuel {
+        fumola::step::Fuel {
+            steps: self.max_steps,
+            proc_steps: self.max_proc_steps,
+            time: self.timeout_ms.map(std::time::Duration::from_millis),
+        }
+    }
+}
+
+/// Output format of `run`.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    System,
+    Store,
+    Html,
+    Chrome,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "system" => Ok(Format::System),
+            "store" => Ok(Format::Store),
+            "html" => Ok(Format::Html),
+            "chrome" => Ok(Format::Chrome),
+            _ => Err(format!(
+                "unknown format {} (expected system, store, html or chrome)",
+                s
+            )),
+        }
+    }
+}
+
+/// Priority of a process, given on the command line as `name=n`.
+#[derive(Debug, Clone)]
+pub struct PriorityOpt(Sym, i32);
+
+impl std::str::FromStr for PriorityOpt {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PriorityOpt, String> {
+        let (name, n) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected name=n, not {}", s))?;
+        let name = fumola::parser::SymParser::new()
+            .parse(name)
+            .map_err(|e| format!("invalid process name {}: {}", name, e))?;
+        let n = n
+            .parse()
+            .map_err(|e| format!("invalid priority {}: {}", n, e))?;
+        Ok(PriorityOpt(name, n))
+    }
+}
+
+/// Store policy of a nest, given on the command line as `path=policy`,
+/// where the path of nest `m` within nest `n` is `n/m`.
+#[derive(Debug, Clone)]
+pub struct NestPolicyOpt(Sym, PutPolicy);
+
+impl std::str::FromStr for NestPolicyOpt {
+    type Err = String;
+    fn from_str(s: &str) -> Result<NestPolicyOpt, String> {
+        let (name, p) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected path=policy, not {}", s))?;
+        let nests = name
+            .split('/')
+            .map(|n| {
+                fumola::parser::SymParser::new()
+                    .parse(n)
+                    .map_err(|e| format!("invalid nest name {}: {}", n, e))
+            })
+            .collect::<Result<Vec<Sym>, String>>()?;
+        let path = fumola::step::nest_path(nests.iter()).unwrap();
+        Ok(NestPolicyOpt(path, p.parse()?))
+    }
+}
+
+fn read_coverage(path: &std::path::Path) -> OurResult<fumola::cover::Coverage> {
+    let err = |e: String| OurError::String(format!("{}: {}", path.display(), e));
+    let text = std::fs::read_to_string(path).map_err(|e| err(e.to_string()))?;
+    let j = serde_json::from_str(&text).map_err(|e| err(e.to_string()))?;
+    fumola::cover::Coverage::from_json(&j).map_err(err)
+}
+
+/// Report unbound variables of a program before running any of it.
+fn check_scope(src: &str, e: &fumola::ast::Exp) -> OurResult<()> {
+    let unbound = fumola::scope::check(e);
+    if unbound.is_empty() {
+        Ok(())
+    } else {
+        eprint!("{}", fumola::scope::report(src, &unbound));
+        Err(OurError::String(format!(
+            "{} unbound variable use(s)",
+            unbound.len()
+        )))
+    }
+}
+
+/// Report type errors, and switches and projections missing labels or
+/// with redundant ones, of a program before running any of it.
+fn check_types(src: &str, e: &fumola::ast::Exp) -> OurResult<()> {
+    let found = fumola::types::exhaustiveness(e);
+    eprint!("{}", fumola::types::exhaustiveness_report(src, &found));
+    let missing = found.iter().filter(|x| !x.missing.is_empty()).count();
+    match fumola::types::check(e) {
+        Ok(t) if missing == 0 => {
+            info!("type: {}", t);
+            Ok(())
+        }
+        Ok(_) => Err(OurError::String(format!(
+            "{} non-exhaustive switch(es) or projection(s)",
+            missing
+        ))),
+        Err(errors) => {
+            eprint!("{}", fumola::types::report(src, &errors));
+            Err(OurError::String(format!("{} type error(s)", errors.len())))
+        }
+    }
+}
+
 fn init_log(level_filter: log::LevelFilter) {
     use env_logger::{Builder, WriteStyle};
     let mut builder = Builder::new();
@@ -69,8 +382,265 @@ fn main() -> OurResult<()> {
     );
     info!("Evaluating CLI command: {:?} ...", &cli_opt.command);
     let () = match cli_opt.command {
-        CliCommand::Check { input: i } => {
-            fumola::check::exp(i.as_str(), None, None).unwrap();
+        CliCommand::Check {
+            input: i,
+            html,
+            chrome,
+            sched,
+            races,
+            fuel,
+            types,
+        } => {
+            let e = fumola::parser::ExpParser::new()
+                .parse(i.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            check_scope(&i, &e)?;
+            if types {
+                check_types(&i, &e)?;
+            }
+            let mut sched = sched.scheduler();
+            let (sys, stop) = fumola::check::run_exp(&e, sched.as_mut(), &fuel.fuel())
+                .map_err(|e| OurError::String(format!("{:?}", e)))?;
+            println!("final system:\n{}", &sys);
+            println!("stopped: {}", stop);
+            let report = fumola::quiesce::report(&sys);
+            if !report.all_halted() {
+                print!("{}", report);
+            }
+            if races {
+                for race in fumola::race::detect(&sys).iter() {
+                    print!("{}", fumola::race::report(&sys, race));
+                }
+            }
+            if let Some(path) = html {
+                std::fs::write(&path, fumola::html::system(&sys))
+                    .map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+                info!("wrote {}", path.display());
+            }
+            if let Some(path) = chrome {
+                std::fs::write(&path, fumola::chrome::system(&sys).to_string())
+                    .map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+                info!("wrote {}", path.display());
+            }
+        }
+        CliCommand::Run {
+            file,
+            format,
+            events,
+            profile,
+            folded,
+            coverage,
+            sched,
+            fuel,
+            types,
+            store,
+            store_history,
+            memo,
+        } => {
+            let input = if file.as_os_str() == "-" {
+                let mut input = String::new();
+                io::stdin()
+                    .read_to_string(&mut input)
+                    .map_err(|e| OurError::String(format!("stdin: {}", e)))?;
+                input
+            } else {
+                std::fs::read_to_string(&file)
+                    .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?
+            };
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            check_scope(&input, &e)?;
+            if types {
+                check_types(&input, &e)?;
+            }
+            let mut sys = fumola::check::system_from_exp(&e)
+                .map_err(|e| OurError::String(format!("{:?}", e)))?;
+            sys.policy = store.policy();
+            let mut sched = sched.scheduler();
+            let mut json = match &events {
+                Some(path) => {
+                    let f = std::fs::File::create(path)
+                        .map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+                    Some(fumola::observe::JsonLines::new(io::BufWriter::new(f)))
+                }
+                None => None,
+            };
+            let mut prof = fumola::profile::Profile::default();
+            let mut cov = fumola::cover::Coverage::default();
+            let mut log = fumola::observe::Log;
+            let mut obs = fumola::observe::Tee(vec![&mut log]);
+            if let Some(json) = json.as_mut() {
+                obs.0.push(json)
+            }
+            if profile || folded.is_some() {
+                obs.0.push(&mut prof)
+            }
+            if coverage.is_some() {
+                obs.0.push(&mut cov)
+            }
+            let mut engine = fumola::incr::Engine::new();
+            let stop = if memo {
+                engine.run_observed(&mut sys, sched.as_mut(), &fuel.fuel(), &mut obs)
+            } else {
+                fumola::step::fully_observed(&mut sys, sched.as_mut(), &fuel.fuel(), &mut obs)
+            };
+            drop(obs);
+            if let (Some(path), Some(mut json)) = (events, json) {
+                let res = match json.error {
+                    Some(e) => Err(e),
+                    None => json.out.flush(),
+                };
+                res.map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+            }
+            if profile {
+                eprint!("{}", prof);
+            }
+            if memo {
+                eprintln!(
+                    "memo: {} nests ran, {} replayed, {} entries",
+                    engine.ran,
+                    engine.reused,
+                    engine.cache.len()
+                );
+                eprint!("{}", engine.cache);
+            }
+            if let Some(path) = coverage {
+                if path.exists() {
+                    cov.merge(&read_coverage(&path)?);
+                }
+                std::fs::write(&path, cov.to_json().to_string())
+                    .map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+            }
+            if let Some(path) = folded {
+                std::fs::write(&path, prof.folded())
+                    .map_err(|e| OurError::String(format!("{}: {}", path.display(), e)))?;
+            }
+            match format {
+                Format::System => print!("{}", sys),
+                Format::Store => println!("{}", sys.store),
+                Format::Html => print!("{}", fumola::html::system(&sys)),
+                Format::Chrome => println!("{}", fumola::chrome::system(&sys)),
+            };
+            if store_history {
+                print!("{}", fumola::history::History::of(&sys));
+            }
+            let report = fumola::quiesce::report(&sys);
+            if !report.all_halted() {
+                eprint!("{}", report);
+            }
+            let outcome = fumola::quiesce::Outcome::of(&stop, &report);
+            info!("stopped: {}; outcome: {:?}", stop, outcome);
+            std::process::exit(outcome.exit_code());
+        }
+        CliCommand::Explore {
+            input,
+            max_states,
+            no_reduce,
+        } => {
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            check_scope(&input, &e)?;
+            let sys = fumola::check::system_from_exp(&e)
+                .map_err(|e| OurError::String(format!("{:?}", e)))?;
+            let explorer = fumola::explore::Explorer {
+                max_states,
+                reduce: !no_reduce,
+            };
+            print!("{}", explorer.explore(&sys));
+        }
+        CliCommand::Coverage { file, coverage } => {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?;
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            let mut cov = fumola::cover::Coverage::default();
+            for path in coverage.iter() {
+                cov.merge(&read_coverage(path)?);
+            }
+            print!("{}", fumola::cover::Report::new(&input, &e, &cov));
+        }
+        CliCommand::Effects { file } => {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?;
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            let effects =
+                fumola::effects::analyze(&e).map_err(|e| OurError::String(format!("{:?}", e)))?;
+            print!("{}", effects);
+        }
+        CliCommand::Names { file } => {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?;
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            let conflicts =
+                fumola::unique::check(&e).map_err(|e| OurError::String(format!("{:?}", e)))?;
+            if !conflicts.is_empty() {
+                print!("{}", fumola::unique::report(&input, &conflicts));
+                return Err(OurError::String(format!(
+                    "{} name conflict(s)",
+                    conflicts.len()
+                )));
+            }
+            println!("all names are distinct");
+        }
+        CliCommand::Lint {
+            file,
+            allow,
+            allow_at,
+        } => {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?;
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            let allow = allow.into_iter().collect();
+            let mut findings = fumola::lint::check(&e, &allow)
+                .map_err(|e| OurError::String(format!("{:?}", e)))?;
+            fumola::lint::allow_at(&input, &mut findings, &allow_at);
+            print!("{}", fumola::lint::report(&input, &findings));
+            let errors = findings
+                .iter()
+                .filter(|f| f.lint.severity() == fumola::lint::Severity::Error)
+                .count();
+            if errors > 0 {
+                return Err(OurError::String(format!("{} lint error(s)", errors)));
+            }
+        }
+        CliCommand::Debug { file } => {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| OurError::String(format!("{}: {}", file.display(), e)))?;
+            let e = fumola::parser::ExpParser::new()
+                .parse(input.as_str())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            check_scope(&input, &e)?;
+            let sys = fumola::check::system_from_exp(&e)
+                .map_err(|e| OurError::String(format!("{:?}", e)))?;
+            let mut debugger = fumola::debug::Debugger::new(sys);
+            debugger
+                .session(io::stdin().lock(), &mut io::stdout())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+        }
+        CliCommand::Lsp => {
+            let mut server = fumola::lsp::Server::new();
+            server
+                .session(io::stdin().lock(), &mut io::stdout())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
+            // Exiting without a shutdown request is an error.
+            if !server.shut_down() {
+                std::process::exit(1);
+            }
+        }
+        CliCommand::Repl { max_steps } => {
+            let mut repl = fumola::repl::Repl::new();
+            repl.max_steps = max_steps;
+            repl.session(io::stdin().lock(), &mut io::stdout())
+                .map_err(|e| OurError::String(format!("{}", e)))?;
         }
         CliCommand::Completions { shell: s } => {
             // see also: https://clap.rs/effortless-auto-completion/
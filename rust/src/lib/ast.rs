@@ -4,6 +4,9 @@ pub enum Exp {
     Spawn(Val, Box<Exp>),
     Put(Val, Val),
     Get(Val),
+    /// GetWait is like Get, but waits for the symbol to be put,
+    /// rather than failing when it is not yet in the store.
+    GetWait(Val),
     Link(Val),
     AssertEq(Val, bool, Val),
     Lambda(Pat, Box<Exp>),
@@ -18,6 +21,16 @@ pub enum Exp {
     LetBx(Pat, Box<Exp>, Box<Exp>),
     Extract(Val),
     Hole,
+    /// Where the expression is in the source text.  Transparent to
+    /// evaluation and printing; used for coverage (see `cover`).
+    Loc(Loc, Box<Exp>),
+}
+
+/// Byte offsets of an expression in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Loc {
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -142,10 +155,44 @@ pub mod step {
 
     /// System representation for stepping repeatedly.
     /// Compared with TraceNet, uses Procs in place of Net.
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     pub struct System {
         pub store: Store,
         pub procs: Procs,
+        /// Number of (global) system steps taken so far.
+        pub step: usize,
+        /// Every event of every process, stamped with its global step.
+        pub timeline: Timeline,
+        /// Number of steps taken by each process so far.
+        pub proc_steps: std::collections::HashMap<Sym, usize>,
+        /// What puts do to symbols already in the store.
+        pub policy: StorePolicy,
+        /// The store as the host left it, taken before the first step.
+        pub seed: Store,
+    }
+
+    /// Put policy.
+    /// What a put does to a symbol that is already in the store.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum PutPolicy {
+        /// Replace its value.
+        #[default]
+        Overwrite,
+        /// Fail with `Error::Overwrite`.
+        WriteOnce,
+        /// Fail with `Error::Overwrite`, unless the value is unchanged.
+        OverwriteIfEqual,
+    }
+
+    /// Store policy of a system:
+    /// a default, and the policies of the puts within named nests.
+    #[derive(Debug, Clone, Default)]
+    pub struct StorePolicy {
+        pub default: PutPolicy,
+        /// By nest path (`n/m` for nest `m` within nest `n`, see
+        /// `step::nest_path`), for the puts in the nest and in its own
+        /// nests, unless a nest closer to the put has a policy too.
+        pub nests: std::collections::HashMap<Sym, PutPolicy>,
     }
 
     #[derive(Debug, Clone)]
@@ -154,13 +201,48 @@ pub mod step {
         Nest(Sym, Vec<Trace>),
         Ret(Val),
         Put(Sym, Val),
+        /// The next put replaces the symbol's value (old value, new value).
+        Overwrite(Sym, Val, Val),
         Get(Sym, Val),
+        /// A get waits for the symbol to be put, before getting it.
+        Wait(Sym),
         Link(Val, Val),
     }
 
     #[derive(Debug, Clone)]
     pub struct Traces(pub Vec<Trace>);
 
+    /// Event.
+    /// An observable action of a single process, in the order it happens.
+    #[derive(Debug, Clone)]
+    pub enum Event {
+        /// Process enters a nest with the given name.
+        NestBegin(Sym),
+        /// Process returns from the nest with the given name.
+        NestEnd(Sym),
+        Put(Sym, Val),
+        Get(Sym, Val),
+        /// Blocking get, once its symbol is put.
+        GetWait(Sym, Val),
+        /// Link to a symbol or to a halted process is resolved.
+        Link(Val, Val),
+        /// Process spawns another process with the given name.
+        Spawn(Sym),
+        /// Process halts with the given return value.
+        Halt(Val),
+    }
+
+    /// Event of a named process, stamped with the global system step.
+    #[derive(Debug, Clone)]
+    pub struct Stamp {
+        pub step: usize,
+        pub proc: Sym,
+        pub event: Event,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct Timeline(pub Vec<Stamp>);
+
     #[derive(Debug, Clone)]
     pub struct Procs(pub std::collections::HashMap<Sym, Proc>);
 
@@ -170,10 +252,10 @@ pub mod step {
     #[derive(Debug, Clone)]
     pub struct Stack(pub Vec<Frame>);
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct ValsEnv(pub std::collections::HashMap<Id, Val>);
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct Env {
         pub vals: ValsEnv,
         pub bxes: BxesEnv,
@@ -197,12 +279,12 @@ pub mod step {
     pub enum Signal {
         /// Process has successfully produced a final return value.
         Halt(Val),
-        /// Process is waiting to link to a symbol not yet in the store,
+        /// Process is waiting to link to (or get) a symbol not yet in the store,
         LinkWaitPtr(Sym),
         /// Process is waiting to link to another process to halt.
         LinkWaitHalt(Sym),
         /// Process is spawning another process with given name, env and body.
-        Spawn(Sym, Env, Exp),
+        Spawn(Sym, Box<Env>, Box<Exp>),
     }
 
     /// Fumola implementation errors.
@@ -270,8 +352,26 @@ pub mod step {
         /// It is an error to name a spawned process a non-uniquely.
         Duplicate(Sym),
 
+        /// Put to a symbol already in the store, against the store policy
+        /// (symbol, old value, new value).
+        Overwrite(Sym, Val, Val),
+
         /// Assertion that v1 and v2 are equal (or not) equal failed.
         AssertionFailure(Val, bool, Val),
+
+        /// Process stopped by a fuel limit, before it finished.
+        OutOfFuel(Limit),
+    }
+
+    /// Fuel limit that stopped stepping.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Limit {
+        /// Global system steps.
+        Steps(usize),
+        /// Steps of a single process.
+        ProcSteps(usize),
+        /// Wall-clock time.
+        Time(std::time::Duration),
     }
 
     #[derive(Debug, Clone)]
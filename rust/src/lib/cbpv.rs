@@ -147,6 +147,7 @@ fn expression<I: Iterator<Item = String>>(
     match e {
         Ret_(_) => unreachable!(),
         Hole => Ok(Hole),
+        Loc(l, e) => Ok(Loc(*l, Box::new(expression(free_vars, bindings, e)?))),
         Extract(v) => {
             let v = value(free_vars, bindings, v)?;
             Ok(Extract(v))
@@ -205,5 +206,9 @@ fn expression<I: Iterator<Item = String>>(
             let v = value(free_vars, bindings, v)?;
             Ok(Get(v))
         }
+        GetWait(v) => {
+            let v = value(free_vars, bindings, v)?;
+            Ok(GetWait(v))
+        }
     }
 }
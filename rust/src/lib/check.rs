@@ -1,8 +1,9 @@
 use crate::ast::{
-    step::{Proc, Procs, Store, System},
+    step::{Proc, Procs, Store, StorePolicy, System, Timeline},
     Exp, Sym,
 };
 use crate::cbpv::FreeVarsNoNext;
+use crate::step::{Fuel, Stop};
 
 use std::collections::HashMap;
 
@@ -31,9 +32,77 @@ pub fn system_from_exp(e: &Exp) -> Result<System, FreeVarsNoNext> {
     Ok(System {
         store: Store(HashMap::new()),
         procs: Procs(procs),
+        step: 0,
+        timeline: Timeline::default(),
+        proc_steps: HashMap::new(),
+        policy: StorePolicy::default(),
+        seed: Store(HashMap::new()),
     })
 }
 
+/// Why a program given as text could not be stepped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    /// The program does not parse (with the parser's message).
+    Parse(String),
+    FreeVars(FreeVarsNoNext),
+}
+
+impl std::convert::From<FreeVarsNoNext> for RunError {
+    fn from(e: FreeVarsNoNext) -> Self {
+        RunError::FreeVars(e)
+    }
+}
+
+/// Parse and convert a program, with the given store policy, without
+/// stepping it.
+pub fn initial_system(input: &str, policy: StorePolicy) -> Result<System, RunError> {
+    let expr = crate::parser::ExpParser::new()
+        .parse(input)
+        .map_err(|e| RunError::Parse(e.to_string()))?;
+    let mut sys = system_from_exp(&expr)?;
+    sys.policy = policy;
+    Ok(sys)
+}
+
+/// Parse, convert and fully step a program, returning the final system.
+pub fn system(input: &str) -> Result<System, RunError> {
+    system_with(input, &mut crate::schedule::ByName)
+}
+
+/// Parse, convert and fully step a program using the given scheduler.
+pub fn system_with(
+    input: &str,
+    sched: &mut dyn crate::schedule::Scheduler,
+) -> Result<System, RunError> {
+    Ok(run(input, sched, &Fuel::default())?.0)
+}
+
+/// Parse, convert and fully step a program using the given scheduler and
+/// fuel, returning the final system and why stepping stopped.
+pub fn run(
+    input: &str,
+    sched: &mut dyn crate::schedule::Scheduler,
+    fuel: &Fuel,
+) -> Result<(System, Stop), RunError> {
+    let expr = crate::parser::ExpParser::new()
+        .parse(input)
+        .map_err(|e| RunError::Parse(e.to_string()))?;
+    Ok(run_exp(&expr, sched, fuel)?)
+}
+
+/// Convert and fully step a parsed program using the given scheduler and
+/// fuel, returning the final system and why stepping stopped.
+pub fn run_exp(
+    e: &Exp,
+    sched: &mut dyn crate::schedule::Scheduler,
+    fuel: &Fuel,
+) -> Result<(System, Stop), FreeVarsNoNext> {
+    let mut sys = system_from_exp(e)?;
+    let stop = crate::step::fully_with(&mut sys, sched, fuel);
+    Ok((sys, stop))
+}
+
 pub fn exp(
     input: &str,
     parse_ast: Option<&str>,
@@ -43,7 +112,7 @@ pub fn exp(
     match parse_ast {
         None => (),
         Some(a) => {
-            assert_eq!(&format!("{:?}", expr), a);
+            assert_eq!(&format!("{:?}", crate::cover::strip(&expr)), a);
         }
     };
     let mut sys = system_from_exp(&expr)?;
@@ -0,0 +1,187 @@
+//! Chrome trace-event export.
+//!
+//! Converts the timeline of a system into the JSON trace-event format
+//! understood by `chrome://tracing`, Perfetto and similar timeline viewers.
+//! Each process is a track (thread), nests are duration spans, and puts,
+//! gets, links, spawns and halts are instant events.  Flow arrows connect
+//! causally-related events: put to get, put (or spawn) to symbol link,
+//! halt to process link, and spawn to the first event of the new process.
+
+use crate::ast::{
+    step::{Event, System},
+    Sym, Val,
+};
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Microseconds of viewer time per global system step.
+pub const STEP_MICROS: usize = 1000;
+
+/// An event position: (track, timestamp).
+type Pos = (usize, usize);
+
+struct Export {
+    events: Vec<Value>,
+    next_flow: usize,
+}
+
+impl Export {
+    fn flow(&mut self, name: &str, from: Pos, to: Pos) {
+        let id = self.next_flow;
+        self.next_flow += 1;
+        self.events.push(json!({
+            "name": name, "cat": "causal", "ph": "s", "id": id,
+            "pid": 1, "tid": from.0, "ts": from.1,
+        }));
+        self.events.push(json!({
+            "name": name, "cat": "causal", "ph": "f", "bp": "e", "id": id,
+            "pid": 1, "tid": to.0, "ts": to.1,
+        }));
+    }
+}
+
+fn instant(name: String, pos: Pos, args: Value) -> Value {
+    json!({
+        "name": name, "cat": "event", "ph": "i", "s": "t",
+        "pid": 1, "tid": pos.0, "ts": pos.1, "args": args,
+    })
+}
+
+/// Export the timeline of a system as a Chrome trace-event JSON value.
+pub fn system(sys: &System) -> Value {
+    let mut names: Vec<&Sym> = sys.procs.0.keys().collect();
+    names.sort();
+    let tracks: HashMap<&Sym, usize> = names.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+    let mut ex = Export {
+        events: vec![json!({
+            "name": "process_name", "ph": "M", "pid": 1, "args": { "name": "fumola" },
+        })],
+        next_flow: 0,
+    };
+    for (i, s) in names.iter().enumerate() {
+        ex.events.push(json!({
+            "name": "thread_name", "ph": "M", "pid": 1, "tid": i,
+            "args": { "name": s.to_string() },
+        }));
+        ex.events.push(json!({
+            "name": "thread_sort_index", "ph": "M", "pid": 1, "tid": i,
+            "args": { "sort_index": i },
+        }));
+    }
+
+    // Several events of one process may share a step; spread them out.
+    let mut last: HashMap<&Sym, usize> = HashMap::new();
+    let mut puts: HashMap<&Sym, Pos> = HashMap::new();
+    let mut halts: HashMap<&Sym, Pos> = HashMap::new();
+    let mut first: HashMap<&Sym, Pos> = HashMap::new();
+    let mut spawns: Vec<(&Sym, Pos)> = vec![];
+    let mut links: Vec<(&Val, Pos)> = vec![];
+    let mut open: HashMap<&Sym, usize> = HashMap::new();
+    let mut end = 0;
+
+    for st in sys.timeline.0.iter() {
+        let track = match tracks.get(&st.proc) {
+            Some(t) => *t,
+            None => continue,
+        };
+        let ts = match last.get(&st.proc) {
+            Some(l) if *l >= st.step * STEP_MICROS => l + 1,
+            _ => st.step * STEP_MICROS,
+        };
+        last.insert(&st.proc, ts);
+        let pos = (track, ts);
+        end = end.max(pos.1 + 1);
+        first.entry(&st.proc).or_insert(pos);
+        match &st.event {
+            Event::NestBegin(s) => {
+                *open.entry(&st.proc).or_insert(0) += 1;
+                ex.events.push(json!({
+                    "name": format!("#{}", s), "cat": "nest", "ph": "B",
+                    "pid": 1, "tid": track, "ts": pos.1,
+                }))
+            }
+            Event::NestEnd(s) => {
+                *open.entry(&st.proc).or_insert(1) -= 1;
+                ex.events.push(json!({
+                    "name": format!("#{}", s), "cat": "nest", "ph": "E",
+                    "pid": 1, "tid": track, "ts": pos.1,
+                }))
+            }
+            Event::Put(s, v) => {
+                puts.insert(s, pos);
+                ex.events.push(instant(
+                    format!("put {}", s),
+                    pos,
+                    json!({ "symbol": s.to_string(), "value": v.to_string() }),
+                ))
+            }
+            Event::Get(s, v) | Event::GetWait(s, v) => {
+                if let Some(from) = puts.get(s) {
+                    ex.flow("put-get", *from, pos)
+                }
+                ex.events.push(instant(
+                    format!("get {}", s),
+                    pos,
+                    json!({ "symbol": s.to_string(), "value": v.to_string() }),
+                ))
+            }
+            Event::Link(v1, v2) => {
+                match v1 {
+                    Val::Sym(s) => {
+                        if let Some(from) = puts.get(s) {
+                            ex.flow("put-link", *from, pos)
+                        }
+                    }
+                    // The halt may not have been seen yet; resolve at the end.
+                    Val::Proc(_) => links.push((v1, pos)),
+                    _ => (),
+                };
+                ex.events.push(instant(
+                    format!("link {}", v1),
+                    pos,
+                    json!({ "target": v1.to_string(), "value": v2.to_string() }),
+                ))
+            }
+            Event::Spawn(s) => {
+                // Store entries of spawned processes are link targets too.
+                puts.insert(s, pos);
+                spawns.push((s, pos));
+                ex.events.push(instant(
+                    format!("spawn {}", s),
+                    pos,
+                    json!({ "process": s.to_string() }),
+                ))
+            }
+            Event::Halt(v) => {
+                halts.insert(&st.proc, pos);
+                ex.events.push(instant(
+                    "halt".to_string(),
+                    pos,
+                    json!({ "value": v.to_string() }),
+                ))
+            }
+        }
+    }
+    for (v, pos) in links.into_iter() {
+        if let Val::Proc(p) = v {
+            if let Some(from) = halts.get(p) {
+                ex.flow("halt-link", *from, pos)
+            }
+        }
+    }
+    for (s, pos) in spawns.into_iter() {
+        if let Some(to) = first.get(s) {
+            ex.flow("spawn", pos, *to)
+        }
+    }
+    // Close the nests of processes that never returned from them.
+    for (p, n) in open.into_iter() {
+        for _ in 0..n {
+            ex.events.push(json!({
+                "ph": "E", "pid": 1, "tid": tracks[p], "ts": end,
+            }))
+        }
+    }
+    json!({ "traceEvents": ex.events, "displayTimeUnit": "ms" })
+}
@@ -0,0 +1,380 @@
+//! Expression coverage.
+//!
+//! The parser wraps each expression in an `Exp::Loc` giving its place in
+//! the source.  `Coverage` observes a run (see `observe`) and counts the
+//! steps taken from each location; coverage of several runs (of the same
+//! source) merges by adding counts.  The report maps locations back to
+//! source lines, listing switch cases and branches that never ran, boxes
+//! never extracted, and asserts never evaluated.
+
+use crate::ast::{Branch, Branches, BxVal, Case, Cases, Exp, Loc, Pat, RecordVal, Val, ValField};
+use crate::observe::{Kind, StepEvent, StepObserver};
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Remove source locations, e.g., to compare parsed expressions.
+pub fn strip(e: &Exp) -> Exp {
+    use Exp::*;
+    let b = |e: &Exp| Box::new(strip(e));
+    match e {
+        Loc(_, e) => strip(e),
+        Nest(v, e) => Nest(strip_val(v), b(e)),
+        Spawn(v, e) => Spawn(strip_val(v), b(e)),
+        Put(v1, v2) => Put(strip_val(v1), strip_val(v2)),
+        Get(v) => Get(strip_val(v)),
+        GetWait(v) => GetWait(strip_val(v)),
+        Link(v) => Link(strip_val(v)),
+        AssertEq(v1, eq, v2) => AssertEq(strip_val(v1), *eq, strip_val(v2)),
+        Lambda(p, e) => Lambda(p.clone(), b(e)),
+        App(e, v) => App(b(e), strip_val(v)),
+        Let(p, e1, e2) => Let(p.clone(), b(e1), b(e2)),
+        Ret(v) => Ret(strip_val(v)),
+        Ret_(v) => Ret_(strip_val(v)),
+        Switch(v, cs) => Switch(strip_val(v), strip_cases(cs)),
+        Branches(bs) => Branches(strip_branches(bs)),
+        Project(e, v) => Project(b(e), strip_val(v)),
+        LetBx(p, e1, e2) => LetBx(p.clone(), b(e1), b(e2)),
+        Extract(v) => Extract(strip_val(v)),
+        Hole => Hole,
+    }
+}
+
+fn strip_val(v: &Val) -> Val {
+    use Val::*;
+    match v {
+        CallByValue(e) => CallByValue(Box::new(strip(e))),
+        Variant(v1, v2) => Variant(Box::new(strip_val(v1)), Box::new(strip_val(v2))),
+        Record(r) => Record(strip_record(r)),
+        RecordExt(v, vf) => RecordExt(Box::new(strip_val(v)), Box::new(strip_field(vf))),
+        Bx(bx) => Bx(Box::new(BxVal {
+            bxes: bx.bxes.clone(),
+            name: bx.name.clone(),
+            code: strip(&bx.code),
+        })),
+        Sym(_) | Ptr(_) | Proc(_) | Var(_) | Num(_) => v.clone(),
+    }
+}
+
+fn strip_field(vf: &ValField) -> ValField {
+    ValField {
+        label: strip_val(&vf.label),
+        value: strip_val(&vf.value),
+    }
+}
+
+fn strip_record(r: &RecordVal) -> RecordVal {
+    RecordVal(r.0.iter().map(strip_field).collect())
+}
+
+fn strip_cases(cs: &Cases) -> Cases {
+    match cs {
+        Cases::Empty => Cases::Empty,
+        Cases::Gather(c1, c2) => {
+            Cases::Gather(Box::new(strip_cases(c1)), Box::new(strip_cases(c2)))
+        }
+        Cases::Case(c) => Cases::Case(Case {
+            label: strip_val(&c.label),
+            pattern: c.pattern.clone(),
+            body: Box::new(strip(&c.body)),
+        }),
+    }
+}
+
+fn strip_branches(bs: &Branches) -> Branches {
+    match bs {
+        Branches::Empty => Branches::Empty,
+        Branches::Gather(b1, b2) => {
+            Branches::Gather(Box::new(strip_branches(b1)), Box::new(strip_branches(b2)))
+        }
+        Branches::Branch(b) => Branches::Branch(Branch {
+            label: strip_val(&b.label),
+            body: Box::new(strip(&b.body)),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PointKind {
+    Exp,
+    Case,
+    Branch,
+    Box,
+    Assert,
+}
+
+/// A source location that coverage reports on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub loc: Loc,
+    pub kind: PointKind,
+    /// What to call the point in the report (e.g., the case label).
+    pub label: String,
+}
+
+/// The coverage points of a (parsed) program: every located expression,
+/// and again for each case and branch body, box code and assert.
+pub fn points(e: &Exp) -> Vec<Point> {
+    let mut ps = vec![];
+    exp_points(&mut ps, e, None);
+    ps.sort_by_key(|p| (p.loc, p.kind));
+    ps.dedup_by_key(|p| (p.loc, p.kind));
+    ps
+}
+
+/// Collect points of `e`; if `e` is located, it is also marked as `mark`.
+fn exp_points(ps: &mut Vec<Point>, e: &Exp, mark: Option<(PointKind, String)>) {
+    use Exp::*;
+    match e {
+        Loc(l, e1) => {
+            let mut point = |kind, label| {
+                ps.push(Point {
+                    loc: *l,
+                    kind,
+                    label,
+                })
+            };
+            point(PointKind::Exp, String::new());
+            if let Some((kind, label)) = mark {
+                point(kind, label)
+            }
+            if let AssertEq(_, _, _) = &**e1 {
+                point(PointKind::Assert, "assert".to_string())
+            }
+            exp_points(ps, e1, None)
+        }
+        Nest(v, e) | Spawn(v, e) => {
+            val_points(ps, v, None);
+            exp_points(ps, e, None)
+        }
+        Put(v1, v2) | AssertEq(v1, _, v2) => {
+            val_points(ps, v1, None);
+            val_points(ps, v2, None)
+        }
+        Get(v) | GetWait(v) | Link(v) | Ret(v) | Ret_(v) | Extract(v) => val_points(ps, v, None),
+        Lambda(_, e) => exp_points(ps, e, None),
+        App(e, v) | Project(e, v) => {
+            exp_points(ps, e, None);
+            val_points(ps, v, None)
+        }
+        Let(_, e1, e2) => {
+            exp_points(ps, e1, None);
+            exp_points(ps, e2, None)
+        }
+        LetBx(p, e1, e2) => {
+            let name = match p {
+                Pat::Var(x) => Some(x.clone()),
+                _ => None,
+            };
+            match crate::step::unloc(e1) {
+                Ret(v) => val_points(ps, v, name),
+                _ => exp_points(ps, e1, None),
+            }
+            exp_points(ps, e2, None)
+        }
+        Switch(v, cs) => {
+            val_points(ps, v, None);
+            case_points(ps, cs)
+        }
+        Branches(bs) => branch_points(ps, bs),
+        Hole => (),
+    }
+}
+
+/// Collect points of `v`; a box value is named `name` unless it names itself.
+fn val_points(ps: &mut Vec<Point>, v: &Val, name: Option<String>) {
+    use Val::*;
+    match v {
+        CallByValue(e) => exp_points(ps, e, None),
+        Variant(v1, v2) => {
+            val_points(ps, v1, None);
+            val_points(ps, v2, None)
+        }
+        Record(r) => {
+            for vf in r.0.iter() {
+                val_points(ps, &vf.label, None);
+                val_points(ps, &vf.value, None)
+            }
+        }
+        RecordExt(v, vf) => {
+            val_points(ps, v, None);
+            val_points(ps, &vf.label, None);
+            val_points(ps, &vf.value, None)
+        }
+        Bx(bx) => {
+            let name = bx.name.clone().or(name).unwrap_or_default();
+            exp_points(
+                ps,
+                &bx.code,
+                Some((PointKind::Box, format!("box {}", name))),
+            )
+        }
+        Sym(_) | Ptr(_) | Proc(_) | Var(_) | Num(_) => (),
+    }
+}
+
+fn case_points(ps: &mut Vec<Point>, cs: &Cases) {
+    match cs {
+        Cases::Empty => (),
+        Cases::Gather(c1, c2) => {
+            case_points(ps, c1);
+            case_points(ps, c2)
+        }
+        Cases::Case(c) => {
+            val_points(ps, &c.label, None);
+            let label = format!("case #{}({})", c.label, c.pattern);
+            exp_points(ps, &c.body, Some((PointKind::Case, label)))
+        }
+    }
+}
+
+fn branch_points(ps: &mut Vec<Point>, bs: &Branches) {
+    match bs {
+        Branches::Empty => (),
+        Branches::Gather(b1, b2) => {
+            branch_points(ps, b1);
+            branch_points(ps, b2)
+        }
+        Branches::Branch(b) => {
+            val_points(ps, &b.label, None);
+            let label = format!("branch {}", b.label);
+            exp_points(ps, &b.body, Some((PointKind::Branch, label)))
+        }
+    }
+}
+
+/// Steps taken from each source location, over one or more runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub hits: HashMap<Loc, usize>,
+}
+
+impl StepObserver for Coverage {
+    fn observe(&mut self, event: &StepEvent) {
+        if let Kind::Stepped { at, .. } = &event.kind {
+            let mut e = at;
+            while let Exp::Loc(l, e1) = e {
+                *self.hits.entry(*l).or_insert(0) += 1;
+                e = e1
+            }
+        }
+    }
+}
+
+impl Coverage {
+    /// Add the coverage of another run (of the same source).
+    pub fn merge(&mut self, other: &Coverage) {
+        for (l, n) in other.hits.iter() {
+            *self.hits.entry(*l).or_insert(0) += n
+        }
+    }
+
+    pub fn hit(&self, l: &Loc) -> bool {
+        self.hits.get(l).cloned().unwrap_or(0) > 0
+    }
+
+    /// As JSON: `{"hits": [[start, end, count], ...]}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut hits: Vec<_> = self.hits.iter().collect();
+        hits.sort();
+        let hits: Vec<_> = hits
+            .into_iter()
+            .map(|(l, n)| serde_json::json!([l.start, l.end, n]))
+            .collect();
+        serde_json::json!({ "hits": hits })
+    }
+
+    pub fn from_json(j: &serde_json::Value) -> Result<Coverage, String> {
+        let mut cov = Coverage::default();
+        let hits = j["hits"]
+            .as_array()
+            .ok_or_else(|| "expected {\"hits\": [...]}".to_string())?;
+        for h in hits.iter() {
+            let n = |i: usize| {
+                h[i].as_u64()
+                    .map(|n| n as usize)
+                    .ok_or_else(|| format!("expected [start, end, count], not {}", h))
+            };
+            *cov.hits
+                .entry(Loc {
+                    start: n(0)?,
+                    end: n(1)?,
+                })
+                .or_insert(0) += n(2)?;
+        }
+        Ok(cov)
+    }
+}
+
+/// 1-based line and column of a byte offset.
+pub fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset.min(src.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, col)
+}
+
+/// Coverage of a program's points, for reporting against its source.
+pub struct Report<'a> {
+    pub src: &'a str,
+    pub points: Vec<Point>,
+    pub coverage: &'a Coverage,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(src: &'a str, e: &Exp, coverage: &'a Coverage) -> Report<'a> {
+        Report {
+            src,
+            points: points(e),
+            coverage,
+        }
+    }
+
+    /// Points of the given kind: how many ran, and how many there are.
+    pub fn count(&self, kind: PointKind) -> (usize, usize) {
+        let ps: Vec<_> = self.points.iter().filter(|p| p.kind == kind).collect();
+        let hit = ps.iter().filter(|p| self.coverage.hit(&p.loc)).count();
+        (hit, ps.len())
+    }
+
+    /// Cases, branches, boxes and asserts that never ran, in source order.
+    pub fn missed(&self) -> Vec<&Point> {
+        self.points
+            .iter()
+            .filter(|p| p.kind != PointKind::Exp && !self.coverage.hit(&p.loc))
+            .collect()
+    }
+}
+
+impl<'a> fmt::Display for Report<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (hit, all) = self.count(PointKind::Exp);
+        write!(f, "coverage: {}/{} expressions", hit, all)?;
+        for (kind, name) in [
+            (PointKind::Case, "cases"),
+            (PointKind::Branch, "branches"),
+            (PointKind::Box, "boxes"),
+            (PointKind::Assert, "asserts"),
+        ] {
+            let (hit, all) = self.count(kind);
+            write!(f, ", {}/{} {}", hit, all, name)?;
+        }
+        writeln!(f)?;
+        let missed = self.missed();
+        if !missed.is_empty() {
+            writeln!(f, "never ran:")?;
+        }
+        for p in missed {
+            let (line, col) = line_col(self.src, p.loc.start);
+            let text = self.src.get(p.loc.start..p.loc.end).unwrap_or("");
+            let text = text.lines().next().unwrap_or("").trim();
+            let text = if text.chars().count() > 40 {
+                format!("{}...", text.chars().take(40).collect::<String>())
+            } else {
+                text.to_string()
+            };
+            writeln!(f, "  {}:{}: {}: {}", line, col, p.label, text)?;
+        }
+        Ok(())
+    }
+}
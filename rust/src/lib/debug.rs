@@ -0,0 +1,294 @@
+//! Interactive step debugger for Fumola processes.
+//!
+//! Commands (one per line):
+//!
+//! - `step <proc>` (`s`): step one process once.
+//! - `round` (`r`): step every process once (one global system step).
+//! - `continue` (`c`): step rounds until a breakpoint hits or nothing can step.
+//! - `break put <sym>`: stop when a process puts the (fully nested) symbol.
+//! - `break error`: stop when any process enters an error state.
+//! - `breaks`, `delete`: list, or delete all, breakpoints.
+//! - `procs` (`p`): list processes, with their status and where they are.
+//! - `inspect <proc>` (`i`): show the env, stack and trace of a process.
+//! - `store`: show the store.
+//! - `help`, `quit` (`q`).
+//!
+//! Where a process is, is shown by the shallow copy of its current
+//! expression (see `step::head`).
+
+use crate::ast::{
+    step::{Event, Proc, System},
+    Sym,
+};
+use crate::schedule::{ByName, Scheduler};
+
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Break {
+    /// Break when a process puts this symbol.
+    Put(Sym),
+    /// Break when a process enters an error state.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(Sym),
+    Round,
+    Continue,
+    Break(Break),
+    Breaks,
+    Delete,
+    Procs,
+    Inspect(Sym),
+    Store,
+    Help,
+    Quit,
+}
+
+const HELP: &str = "commands:
+  step <proc>        step one process once            (s)
+  round              step every process once          (r)
+  continue           step until a breakpoint hits     (c)
+  break put <sym>    break when <sym> is put
+  break error        break when a process fails
+  breaks             list breakpoints
+  delete             delete all breakpoints
+  procs              list processes                   (p)
+  inspect <proc>     show env, stack and trace        (i)
+  store              show the store
+  quit                                                (q)
+";
+
+fn sym(s: &str) -> Result<Sym, String> {
+    if s == "%" {
+        return Ok(Sym::None);
+    }
+    crate::parser::SymParser::new()
+        .parse(s)
+        .map_err(|e| format!("invalid symbol {}: {}", s, e))
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+    fn from_str(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["step", p] | ["s", p] => Ok(Command::Step(sym(p)?)),
+            ["round"] | ["r"] => Ok(Command::Round),
+            ["continue"] | ["c"] => Ok(Command::Continue),
+            ["break", "put", s] | ["b", "put", s] => Ok(Command::Break(Break::Put(sym(s)?))),
+            ["break", "error"] | ["b", "error"] => Ok(Command::Break(Break::Error)),
+            ["breaks"] => Ok(Command::Breaks),
+            ["delete"] => Ok(Command::Delete),
+            ["procs"] | ["p"] => Ok(Command::Procs),
+            ["inspect", p] | ["i", p] => Ok(Command::Inspect(sym(p)?)),
+            ["store"] => Ok(Command::Store),
+            ["help"] | ["h"] | ["?"] => Ok(Command::Help),
+            ["quit"] | ["q"] => Ok(Command::Quit),
+            _ => Err(format!("unknown command: {} (try help)", line.trim())),
+        }
+    }
+}
+
+/// One-line status of a process, and where it is.
+pub fn status(p: &Proc) -> String {
+    use crate::step::head;
+    match p {
+        Proc::Spawn(e) => format!("spawned, at {}", head(e)),
+        Proc::Running(r) => format!("running, at {}", head(&r.cont)),
+        Proc::WaitingForPtr(r, s) => {
+            format!("waiting for pointer {}, at {}", s, head(&r.cont))
+        }
+        Proc::WaitingForHalt(r, s) => {
+            format!("waiting for process {}, at {}", s, head(&r.cont))
+        }
+        Proc::Error(r, e) => format!("error {}, at {}", e, head(&r.cont)),
+        Proc::Halted(h) => format!("halted with {}", h.retval),
+    }
+}
+
+pub struct Debugger {
+    pub sys: System,
+    pub sched: Box<dyn Scheduler>,
+    pub breaks: Vec<Break>,
+}
+
+impl Debugger {
+    pub fn new(sys: System) -> Debugger {
+        Debugger {
+            sys,
+            sched: Box::new(ByName),
+            breaks: vec![],
+        }
+    }
+
+    /// Breakpoints hit since the timeline had `events` events,
+    /// given the processes that had failed before.
+    fn hits(&self, events: usize, failed: &[Sym]) -> Vec<String> {
+        let mut hits = vec![];
+        for b in self.breaks.iter() {
+            match b {
+                Break::Put(s) => {
+                    for st in self.sys.timeline.0[events..].iter() {
+                        if let Event::Put(s2, v) = &st.event {
+                            if s2 == s {
+                                hits.push(format!("breakpoint: {} put {} <= {}", st.proc, s, v))
+                            }
+                        }
+                    }
+                }
+                Break::Error => {
+                    for (p, e) in self.failed_procs() {
+                        if !failed.contains(&p) {
+                            hits.push(format!("breakpoint: {} failed: {}", p, e))
+                        }
+                    }
+                }
+            }
+        }
+        hits
+    }
+
+    fn failed_procs(&self) -> Vec<(Sym, String)> {
+        let mut ps: Vec<_> = self
+            .sys
+            .procs
+            .0
+            .iter()
+            .filter_map(|(s, p)| match p {
+                Proc::Error(_, e) => Some((s.clone(), format!("{}", e))),
+                _ => None,
+            })
+            .collect();
+        ps.sort();
+        ps
+    }
+
+    /// Take a step (one process, or a round), reporting breakpoint hits.
+    fn step(&mut self, out: &mut dyn Write, proc: Option<&Sym>) -> io::Result<Vec<String>> {
+        let events = self.sys.timeline.0.len();
+        let failed: Vec<Sym> = self.failed_procs().into_iter().map(|p| p.0).collect();
+        let stepped = match proc {
+            Some(s) => crate::step::one(&mut self.sys, s).is_ok(),
+            None => crate::step::system_with(&mut self.sys, self.sched.as_mut()).is_ok(),
+        };
+        if !stepped {
+            writeln!(out, "no step")?;
+            return Ok(vec![]);
+        }
+        for st in self.sys.timeline.0[events..].iter() {
+            writeln!(out, "  {}: {}", st.proc, st.event)?;
+        }
+        let hits = self.hits(events, &failed);
+        for h in hits.iter() {
+            writeln!(out, "{}", h)?;
+        }
+        Ok(hits)
+    }
+
+    fn procs(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut ps: Vec<_> = self.sys.procs.0.keys().collect();
+        ps.sort();
+        for p in ps {
+            writeln!(out, "{}: {}", p, status(&self.sys.procs.0[p]))?;
+        }
+        Ok(())
+    }
+
+    fn inspect(&self, out: &mut dyn Write, p: &Sym) -> io::Result<()> {
+        let proc = match self.sys.procs.0.get(p) {
+            Some(proc) => proc,
+            None => return writeln!(out, "no process {}", p),
+        };
+        writeln!(out, "{}: {}", p, status(proc))?;
+        match proc {
+            Proc::Running(r)
+            | Proc::WaitingForPtr(r, _)
+            | Proc::WaitingForHalt(r, _)
+            | Proc::Error(r, _) => {
+                writeln!(out, "  vals = {}", r.env.vals)?;
+                writeln!(out, "  bxes = {}", r.env.bxes)?;
+                writeln!(out, "  stack = {}", r.stack)?;
+            }
+            Proc::Spawn(_) | Proc::Halted(_) => (),
+        }
+        let trace: Vec<String> = crate::step::proc_traces(proc)
+            .iter()
+            .map(|t| format!("{}", t))
+            .collect();
+        writeln!(out, "  trace = [{}]", trace.join("; "))
+    }
+
+    /// Run one command.  Returns false when the session should end.
+    pub fn command(&mut self, cmd: &Command, out: &mut dyn Write) -> io::Result<bool> {
+        match cmd {
+            Command::Step(p) => {
+                if self.sys.procs.0.contains_key(p) {
+                    self.step(out, Some(p))?;
+                    writeln!(out, "{}: {}", p, status(&self.sys.procs.0[p]))?;
+                } else {
+                    writeln!(out, "no process {}", p)?;
+                }
+            }
+            Command::Round => {
+                self.step(out, None)?;
+            }
+            Command::Continue => loop {
+                let events = self.sys.timeline.0.len();
+                let step = self.sys.step;
+                let hits = self.step(out, None)?;
+                if !hits.is_empty() {
+                    break;
+                }
+                if self.sys.step == step && self.sys.timeline.0.len() == events {
+                    self.procs(out)?;
+                    break;
+                }
+            },
+            Command::Break(b) => {
+                if !self.breaks.contains(b) {
+                    self.breaks.push(b.clone())
+                }
+            }
+            Command::Breaks => {
+                for b in self.breaks.iter() {
+                    match b {
+                        Break::Put(s) => writeln!(out, "break put {}", s)?,
+                        Break::Error => writeln!(out, "break error")?,
+                    }
+                }
+            }
+            Command::Delete => self.breaks.clear(),
+            Command::Procs => self.procs(out)?,
+            Command::Inspect(p) => self.inspect(out, p)?,
+            Command::Store => writeln!(out, "{}", self.sys.store)?,
+            Command::Help => write!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        };
+        Ok(true)
+    }
+
+    /// Read commands until `quit` or end of input.
+    pub fn session<R: BufRead>(&mut self, input: R, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "(fumola) ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                match line.parse::<Command>() {
+                    Ok(cmd) => {
+                        if !self.command(&cmd, out)? {
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => writeln!(out, "{}", e)?,
+                }
+            }
+            write!(out, "(fumola) ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+}
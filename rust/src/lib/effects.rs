@@ -0,0 +1,582 @@
+//! Store effects, without running.
+//!
+//! An abstract interpretation of a program that approximates the store
+//! symbols it may put, get and link to, and the names of the processes it
+//! may spawn.  Symbols are patterns: a symbol known from the program
+//! text, `*` for a symbol computed at runtime, or `n/s` for a symbol put
+//! within nest `n` (as `step::put_symbol` prefixes it).  Every symbol the
+//! program uses matches some pattern of the right set; the converse may
+//! not hold.
+//!
+//! Values are tracked through `let`, patterns and box calls; a box called
+//! again from within itself is analyzed once more with unknown arguments,
+//! which covers all deeper calls.  The store is not tracked: values read
+//! from it are unknown, and a box of unknown code (read from it, say) may
+//! put, get, link to and spawn any symbol, any number of times.
+
+use crate::ast::{Branches, BxVal, Cases, Exp, Id, Loc, Pat, Sym, Val};
+use crate::cbpv::FreeVarsNoNext;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// Pattern of a store symbol, or of a process name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SymPat {
+    /// Any symbol.
+    Any,
+    Sym(Sym),
+    /// Symbol put within a nest.
+    Nest(Box<SymPat>, Box<SymPat>),
+}
+
+impl SymPat {
+    pub fn matches(&self, s: &Sym) -> bool {
+        match (self, s) {
+            (SymPat::Any, _) => true,
+            (SymPat::Sym(p), s) => p == s,
+            (SymPat::Nest(pn, ps), Sym::Nest(n, s)) => pn.matches(n) && ps.matches(s),
+            (SymPat::Nest(_, _), _) => false,
+        }
+    }
+
+    /// Whether some symbol matches both patterns.
+    pub fn overlaps(&self, other: &SymPat) -> bool {
+        use SymPat::*;
+        match (self, other) {
+            (Any, _) | (_, Any) => true,
+            (Sym(s1), Sym(s2)) => s1 == s2,
+            (Nest(n1, s1), Nest(n2, s2)) => n1.overlaps(n2) && s1.overlaps(s2),
+            (Nest(n, s), Sym(crate::ast::Sym::Nest(n2, s2)))
+            | (Sym(crate::ast::Sym::Nest(n2, s2)), Nest(n, s)) => {
+                n.overlaps(&Sym((**n2).clone())) && s.overlaps(&Sym((**s2).clone()))
+            }
+            (Nest(_, _), Sym(_)) | (Sym(_), Nest(_, _)) => false,
+        }
+    }
+
+    /// Whether the pattern matches exactly one symbol.
+    pub fn exact(&self) -> bool {
+        match self {
+            SymPat::Any => false,
+            SymPat::Sym(_) => true,
+            SymPat::Nest(n, s) => n.exact() && s.exact(),
+        }
+    }
+
+    fn join(&self, other: &SymPat) -> SymPat {
+        match (self, other) {
+            (p1, p2) if p1 == p2 => p1.clone(),
+            (SymPat::Nest(n1, s1), SymPat::Nest(n2, s2)) => {
+                SymPat::Nest(Box::new(n1.join(n2)), Box::new(s1.join(s2)))
+            }
+            _ => SymPat::Any,
+        }
+    }
+}
+
+/// Symbols that a program may use, by how it uses them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effects {
+    pub puts: BTreeSet<SymPat>,
+    pub gets: BTreeSet<SymPat>,
+    /// Symbols linked to (with `&$s`); links to processes are not included.
+    pub links: BTreeSet<SymPat>,
+    pub spawns: BTreeSet<SymPat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SiteKind {
+    Put,
+    Get,
+    Spawn,
+}
+
+/// A put, get or spawn, as met by the analysis: once for each way the program
+/// may reach it (through box calls, say).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site {
+    pub kind: SiteKind,
+    /// Name put or gotten, or of the process spawned (spawning also puts
+    /// its name).
+    pub sym: SymPat,
+    pub loc: Option<Loc>,
+    /// Within a recursive call, standing for any number of calls.
+    pub many: bool,
+    /// Within a box of unknown code, standing for any number of puts
+    /// (or spawns) of any symbols.
+    pub unknown: bool,
+    /// Alternatives (switch cases or branches) taken to reach the site,
+    /// as pairs of choice and alternative.
+    path: Vec<(usize, usize)>,
+}
+
+impl Site {
+    /// Whether two sites are in different alternatives of a choice,
+    /// so that at most one of them runs.
+    pub fn exclusive(&self, other: &Site) -> bool {
+        self.path
+            .iter()
+            .any(|(c, a)| other.path.iter().any(|(c2, a2)| c == c2 && a != a2))
+    }
+}
+
+/// Abstract value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AVal {
+    /// Not known.
+    Top,
+    Num(i32),
+    Sym(SymPat),
+    Ptr(SymPat),
+    Proc(SymPat),
+    Variant(Box<AVal>, Box<AVal>),
+    Record(Vec<(AVal, AVal)>),
+    Bx(Box<BxVal>),
+}
+
+impl AVal {
+    fn join(self, other: AVal) -> AVal {
+        match (self, other) {
+            (v1, v2) if v1 == v2 => v1,
+            (AVal::Sym(p1), AVal::Sym(p2)) => AVal::Sym(p1.join(&p2)),
+            (AVal::Ptr(p1), AVal::Ptr(p2)) => AVal::Ptr(p1.join(&p2)),
+            (AVal::Proc(p1), AVal::Proc(p2)) => AVal::Proc(p1.join(&p2)),
+            _ => AVal::Top,
+        }
+    }
+
+    /// Pattern of the symbols that this value may be.
+    fn sym(&self) -> SymPat {
+        match self {
+            AVal::Sym(p) => p.clone(),
+            _ => SymPat::Any,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Env {
+    vals: HashMap<Id, AVal>,
+    bxes: HashMap<Id, BxVal>,
+}
+
+/// Pending application or projection, as on the stack of `step::running`.
+#[derive(Clone)]
+enum Frame {
+    App(AVal),
+    Project(AVal),
+}
+
+struct Call {
+    bx: BxVal,
+    /// Called with unknown arguments, for all deeper recursive calls.
+    unknown: bool,
+    /// Nests around the call.
+    nests: usize,
+}
+
+#[derive(Default)]
+struct Analysis {
+    effects: Effects,
+    /// Boxes being called.
+    active: Vec<Call>,
+    /// Number of recursive calls being analyzed that may nest deeper
+    /// with each call; within them, nested symbols are unknown.
+    deepening: usize,
+    /// Values that spawned processes may halt with.
+    halts: HashMap<SymPat, AVal>,
+    sites: Vec<Site>,
+    loc: Option<Loc>,
+    /// Alternatives taken, and the last choice or alternative numbered.
+    path: Vec<(usize, usize)>,
+    alts: usize,
+}
+
+impl Analysis {
+    /// Symbol put (or process spawned) within the given nests.
+    fn put_symbol(&self, nests: &[SymPat], s: SymPat) -> SymPat {
+        if self.deepening > 0 && !nests.is_empty() {
+            return SymPat::Any;
+        }
+        nests
+            .iter()
+            .rev()
+            .fold(s, |s, n| SymPat::Nest(Box::new(n.clone()), Box::new(s)))
+    }
+
+    fn site(&mut self, kind: SiteKind, sym: &SymPat) {
+        self.sites.push(Site {
+            kind,
+            sym: sym.clone(),
+            loc: self.loc,
+            many: self.active.iter().any(|c| c.unknown),
+            unknown: false,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Extract a box whose code is not known: it may use any symbol.
+    fn unknown_call(&mut self, stack: &mut Vec<Frame>) -> AVal {
+        for set in [
+            &mut self.effects.puts,
+            &mut self.effects.gets,
+            &mut self.effects.links,
+            &mut self.effects.spawns,
+        ] {
+            set.insert(SymPat::Any);
+        }
+        // Spawning puts the process name, so one put site covers both.
+        self.site(SiteKind::Put, &SymPat::Any);
+        if let Some(s) = self.sites.last_mut() {
+            s.unknown = true
+        }
+        stack.clear();
+        AVal::Top
+    }
+
+    fn exp(&mut self, env: &Env, nests: &[SymPat], stack: &mut Vec<Frame>, e: &Exp) -> AVal {
+        use Exp::*;
+        match e {
+            Loc(l, e) => {
+                let outer = self.loc.replace(*l);
+                let v = self.exp(env, nests, stack, e);
+                self.loc = outer;
+                v
+            }
+            Hole => AVal::Top,
+            Ret(v) | Ret_(v) => self.val(env, v),
+            Put(v1, v2) => {
+                let s = self.val(env, v1).sym();
+                let s = self.put_symbol(nests, s);
+                self.val(env, v2);
+                self.effects.puts.insert(s.clone());
+                self.site(SiteKind::Put, &s);
+                AVal::Ptr(s)
+            }
+            Get(v) | GetWait(v) => {
+                let s = match self.val(env, v) {
+                    AVal::Ptr(s) => s,
+                    _ => SymPat::Any,
+                };
+                self.effects.gets.insert(s.clone());
+                self.site(SiteKind::Get, &s);
+                AVal::Top
+            }
+            Link(v) => match self.val(env, v) {
+                // Only a process of an exact name is known to be spawned once.
+                AVal::Proc(p) if p.exact() => self.halts.get(&p).cloned().unwrap_or(AVal::Top),
+                AVal::Proc(_) => AVal::Top,
+                v => {
+                    let s = v.sym();
+                    self.effects.links.insert(s.clone());
+                    AVal::Ptr(s)
+                }
+            },
+            AssertEq(_, _, _) => AVal::Record(vec![]),
+            Nest(v, e) => {
+                let mut nests = nests.to_vec();
+                nests.push(self.val(env, v).sym());
+                self.exp(env, &nests, stack, e)
+            }
+            Spawn(v, e) => {
+                let s = self.val(env, v).sym();
+                let s = self.put_symbol(nests, s);
+                self.effects.spawns.insert(s.clone());
+                self.site(SiteKind::Spawn, &s);
+                // The new process starts with an empty stack, outside of any nest.
+                let v = self.exp(env, &[], &mut vec![], e);
+                let v = match self.halts.remove(&s) {
+                    Some(h) => h.join(v),
+                    None => v,
+                };
+                self.halts.insert(s.clone(), v);
+                AVal::Proc(s)
+            }
+            App(e, v) => {
+                let v = self.val(env, v);
+                stack.push(Frame::App(v));
+                self.exp(env, nests, stack, e)
+            }
+            Project(e, v) => {
+                let v = self.val(env, v);
+                stack.push(Frame::Project(v));
+                self.exp(env, nests, stack, e)
+            }
+            Lambda(p, e) => {
+                let v = match stack.pop() {
+                    Some(Frame::App(v)) => v,
+                    _ => AVal::Top,
+                };
+                let mut env = env.clone();
+                self.pattern(&mut env, p, &v);
+                self.exp(&env, nests, stack, e)
+            }
+            Let(p, e1, e2) => {
+                let v = self.exp(env, nests, &mut vec![], e1);
+                let mut env = env.clone();
+                self.pattern(&mut env, p, &v);
+                self.exp(&env, nests, stack, e2)
+            }
+            LetBx(p, e1, e2) => {
+                let v = self.exp(env, nests, &mut vec![], e1);
+                let mut env = env.clone();
+                if let (Pat::Var(x), AVal::Bx(bx)) = (p, v) {
+                    env.bxes.insert(x.clone(), *bx);
+                }
+                self.exp(&env, nests, stack, e2)
+            }
+            Extract(Val::Var(x)) => match env.bxes.get(x) {
+                Some(bx) => self.call(bx, nests, stack),
+                None => self.unknown_call(stack),
+            },
+            Extract(v) => match self.val(env, v) {
+                AVal::Bx(bx) => self.call(&bx, nests, stack),
+                _ => self.unknown_call(stack),
+            },
+            Switch(v, cs) => {
+                let v = self.val(env, v);
+                let mut r = None;
+                self.alts += 1;
+                self.cases(env, nests, stack, (self.alts, &v), cs, &mut r);
+                r.unwrap_or(AVal::Top)
+            }
+            Branches(bs) => {
+                let l = match stack.pop() {
+                    Some(Frame::Project(l)) => l,
+                    _ => AVal::Top,
+                };
+                let mut r = None;
+                self.alts += 1;
+                self.branches(env, nests, stack, (self.alts, &l), bs, &mut r);
+                r.unwrap_or(AVal::Top)
+            }
+        }
+    }
+
+    /// Extract a box, with its code reading arguments from `stack`.
+    fn call(&mut self, bx: &BxVal, nests: &[SymPat], stack: &mut Vec<Frame>) -> AVal {
+        let mut env = Env::default();
+        if let Some(name) = &bx.name {
+            env.vals
+                .insert(name.clone(), AVal::Bx(Box::new(bx.clone())));
+        }
+        let calls: Vec<(usize, bool)> = self
+            .active
+            .iter()
+            .filter(|c| c.bx == *bx)
+            .map(|c| (c.nests, c.unknown))
+            .collect();
+        match calls.first() {
+            None => {
+                self.active.push(Call {
+                    bx: bx.clone(),
+                    unknown: false,
+                    nests: nests.len(),
+                });
+                let v = self.exp(&env, nests, stack, &bx.code);
+                self.active.pop();
+                v
+            }
+            Some((first, _)) => {
+                // Recursive call: cover this and all deeper calls at once.
+                if !calls.iter().any(|(_, unknown)| *unknown) {
+                    let deepening = (nests.len() > *first) as usize;
+                    self.active.push(Call {
+                        bx: bx.clone(),
+                        unknown: true,
+                        nests: nests.len(),
+                    });
+                    self.deepening += deepening;
+                    self.exp(&env, nests, &mut vec![], &bx.code);
+                    self.deepening -= deepening;
+                    self.active.pop();
+                }
+                stack.clear();
+                AVal::Top
+            }
+        }
+    }
+
+    /// Analyze one alternative of a choice.
+    fn alternative(&mut self, choice: usize, f: impl FnOnce(&mut Analysis) -> AVal) -> AVal {
+        self.alts += 1;
+        self.path.push((choice, self.alts));
+        let v = f(self);
+        self.path.pop();
+        v
+    }
+
+    /// Join of the results of the branches that may run.
+    fn join(r: &mut Option<AVal>, v: AVal) {
+        *r = Some(match r.take() {
+            None => v,
+            Some(r) => r.join(v),
+        })
+    }
+
+    fn cases(
+        &mut self,
+        env: &Env,
+        nests: &[SymPat],
+        stack: &[Frame],
+        (choice, v): (usize, &AVal),
+        cs: &Cases,
+        r: &mut Option<AVal>,
+    ) {
+        match cs {
+            Cases::Empty => (),
+            Cases::Gather(cs1, cs2) => {
+                self.cases(env, nests, stack, (choice, v), cs1, r);
+                self.cases(env, nests, stack, (choice, v), cs2, r)
+            }
+            Cases::Case(c) => {
+                let label = self.val(env, &c.label);
+                let payload = match v {
+                    AVal::Variant(l, p) if **l == label => (**p).clone(),
+                    AVal::Variant(l, _) if known(l) && known(&label) => return,
+                    _ => AVal::Top,
+                };
+                let mut env = env.clone();
+                self.pattern(&mut env, &c.pattern, &payload);
+                let res =
+                    self.alternative(choice, |a| a.exp(&env, nests, &mut stack.to_vec(), &c.body));
+                Self::join(r, res)
+            }
+        }
+    }
+
+    fn branches(
+        &mut self,
+        env: &Env,
+        nests: &[SymPat],
+        stack: &[Frame],
+        (choice, l): (usize, &AVal),
+        bs: &Branches,
+        r: &mut Option<AVal>,
+    ) {
+        match bs {
+            Branches::Empty => (),
+            Branches::Gather(bs1, bs2) => {
+                self.branches(env, nests, stack, (choice, l), bs1, r);
+                self.branches(env, nests, stack, (choice, l), bs2, r)
+            }
+            Branches::Branch(b) => {
+                let label = self.val(env, &b.label);
+                if label != *l && known(l) && known(&label) {
+                    return;
+                }
+                let res =
+                    self.alternative(choice, |a| a.exp(env, nests, &mut stack.to_vec(), &b.body));
+                Self::join(r, res)
+            }
+        }
+    }
+
+    fn pattern(&mut self, env: &mut Env, p: &Pat, v: &AVal) {
+        match p {
+            Pat::Ignore => (),
+            Pat::Var(x) => drop(env.vals.insert(x.clone(), v.clone())),
+            Pat::Fields(fps) => {
+                for fp in fps.0.iter() {
+                    let label = self.val(env, &fp.label);
+                    let fv = match v {
+                        AVal::Record(fs) => fs
+                            .iter()
+                            .find(|(l, _)| *l == label)
+                            .map(|(_, fv)| fv.clone())
+                            .unwrap_or(AVal::Top),
+                        _ => AVal::Top,
+                    };
+                    self.pattern(env, &fp.pattern, &fv)
+                }
+            }
+            Pat::Case(_) => (),
+        }
+    }
+
+    fn val(&mut self, env: &Env, v: &Val) -> AVal {
+        match v {
+            Val::Num(n) => AVal::Num(*n),
+            Val::Sym(s) => AVal::Sym(SymPat::Sym(s.clone())),
+            Val::Ptr(s) => AVal::Ptr(SymPat::Sym(s.clone())),
+            Val::Proc(s) => AVal::Proc(SymPat::Sym(s.clone())),
+            Val::Var(x) => env.vals.get(x).cloned().unwrap_or(AVal::Top),
+            Val::Variant(l, v) => {
+                AVal::Variant(Box::new(self.val(env, l)), Box::new(self.val(env, v)))
+            }
+            Val::Record(r) => AVal::Record(
+                r.0.iter()
+                    .map(|f| (self.val(env, &f.label), self.val(env, &f.value)))
+                    .collect(),
+            ),
+            Val::Bx(bx) => AVal::Bx(bx.clone()),
+            // Not in converted programs (see `cbpv`).
+            Val::CallByValue(_) | Val::RecordExt(_, _) => AVal::Top,
+        }
+    }
+}
+
+/// Whether a label is exactly known.
+fn known(l: &AVal) -> bool {
+    matches!(l, AVal::Sym(SymPat::Sym(_)) | AVal::Num(_))
+}
+
+/// Effects of a program, after converting it as for stepping (see
+/// `check::system_from_exp`), so that call-by-value forms take effect
+/// where they run.
+pub fn analyze(e: &Exp) -> Result<Effects, FreeVarsNoNext> {
+    Ok(run(e)?.effects)
+}
+
+/// Puts, gets and spawns of a program, in the order the analysis meets them
+/// (see `analyze`).
+pub fn sites(e: &Exp) -> Result<Vec<Site>, FreeVarsNoNext> {
+    Ok(run(e)?.sites)
+}
+
+fn run(e: &Exp) -> Result<Analysis, FreeVarsNoNext> {
+    let mut fv = crate::check::FreeVars {
+        base: "_t_".to_string(),
+        index: 0,
+    };
+    let e = crate::cbpv::convert(&mut fv, e)?;
+    let mut a = Analysis::default();
+    a.exp(&Env::default(), &[], &mut vec![], &e);
+    Ok(a)
+}
+
+impl fmt::Display for SymPat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymPat::Any => write!(f, "*"),
+            SymPat::Sym(s) => write!(f, "{}", s),
+            SymPat::Nest(n, s) => write!(f, "{}/{}", n, s),
+        }
+    }
+}
+
+impl fmt::Display for SiteKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SiteKind::Put => write!(f, "put"),
+            SiteKind::Get => write!(f, "get"),
+            SiteKind::Spawn => write!(f, "spawn"),
+        }
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, set) in [
+            ("puts", &self.puts),
+            ("gets", &self.gets),
+            ("links", &self.links),
+            ("spawns", &self.spawns),
+        ] {
+            let pats: Vec<_> = set.iter().map(|p| p.to_string()).collect();
+            writeln!(f, "{}: {}", name, pats.join(", "))?;
+        }
+        Ok(())
+    }
+}
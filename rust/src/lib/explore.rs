@@ -0,0 +1,219 @@
+//! Exhaustive interleaving exploration (model checking).
+//!
+//! Starting from a system, enumerate the interleavings of its processes at
+//! the granularity of single process steps (`step::one`), and report every
+//! reachable final system (where no process can step) and every schedule
+//! that leads some process into `Proc::Error`.
+//!
+//! Visited states are keyed by their printed form, so that each is expanded
+//! once (and distinct states never collide).  With partial-order reduction, a process whose next step
+//! is local (it neither touches the store nor other processes) is stepped
+//! alone, since that step commutes with the steps of every other process.
+
+use crate::ast::{
+    step::{Error, Proc, System},
+    Exp, Sym,
+};
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Exploration settings.
+#[derive(Debug, Clone)]
+pub struct Explorer {
+    /// Stop after expanding this many distinct states.
+    pub max_states: usize,
+    /// Use partial-order reduction for local steps.
+    pub reduce: bool,
+}
+
+impl Default for Explorer {
+    fn default() -> Self {
+        Explorer {
+            max_states: 100_000,
+            reduce: true,
+        }
+    }
+}
+
+/// The processes stepped, in order, from the initial system.
+pub type Schedule = Vec<Sym>;
+
+/// A reachable final system, with one schedule that reaches it.
+#[derive(Debug, Clone)]
+pub struct Final {
+    pub schedule: Schedule,
+    pub system: System,
+}
+
+/// A schedule whose last step puts a process into an error state.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub schedule: Schedule,
+    pub proc: Sym,
+    pub error: Error,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Exploration {
+    /// Distinct final systems.
+    pub finals: Vec<Final>,
+    /// Distinct (process, error) failures, each with one schedule.
+    pub failures: Vec<Failure>,
+    /// Number of distinct states expanded.
+    pub states: usize,
+    /// Whether `max_states` cut the exploration short.
+    pub truncated: bool,
+}
+
+fn state_key(sys: &System) -> String {
+    format!("{}", sys)
+}
+
+/// Is the next step of the process independent of every other process?
+/// Local steps neither read nor write the store, spawn, link or halt.
+pub fn local(p: &Proc) -> bool {
+    match p {
+        Proc::Spawn(_) => true,
+        Proc::Running(r) => match crate::step::unloc(&r.cont) {
+            Exp::Put(_, _) | Exp::Get(_) | Exp::GetWait(_) | Exp::Link(_) | Exp::Spawn(_, _) => {
+                false
+            }
+            Exp::Ret(_) | Exp::Ret_(_) => !r.stack.0.is_empty(),
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+fn error_of(p: Option<&Proc>) -> Option<&Error> {
+    match p {
+        Some(Proc::Error(_, e)) => Some(e),
+        _ => None,
+    }
+}
+
+impl Explorer {
+    pub fn explore(&self, sys: &System) -> Exploration {
+        let mut ex = Exploration::default();
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut finals: HashSet<String> = HashSet::new();
+        let mut failures: HashSet<(Sym, String)> = HashSet::new();
+        let mut todo: Vec<(System, Schedule)> = vec![(sys.clone(), vec![])];
+        while let Some((sys, schedule)) = todo.pop() {
+            let key = state_key(&sys);
+            if !visited.insert(key.clone()) {
+                continue;
+            }
+            if ex.states >= self.max_states {
+                ex.truncated = true;
+                break;
+            }
+            ex.states += 1;
+            let mut names: Vec<&Sym> = sys
+                .procs
+                .0
+                .iter()
+                .filter(|(_, p)| crate::step::can_step(&sys, p))
+                .map(|(s, _)| s)
+                .collect();
+            names.sort();
+            if names.is_empty() {
+                if finals.insert(key) {
+                    ex.finals.push(Final {
+                        schedule,
+                        system: sys,
+                    });
+                }
+                continue;
+            }
+            let mut succs = vec![];
+            for s in names.iter() {
+                let mut next = sys.clone();
+                let _ = crate::step::one(&mut next, s);
+                succs.push(((*s).clone(), next));
+            }
+            if self.reduce {
+                // Step a single local process, unless that closes a cycle
+                // (in which case every process must get its turn).
+                let ample = succs.iter().position(|(s, next)| {
+                    local(&sys.procs.0[s]) && !visited.contains(&state_key(next))
+                });
+                if let Some(i) = ample {
+                    succs = vec![succs.swap_remove(i)];
+                }
+            }
+            // Push in reverse so that name order is explored first.
+            for (s, next) in succs.into_iter().rev() {
+                let mut schedule = schedule.clone();
+                schedule.push(s.clone());
+                if error_of(sys.procs.0.get(&s)).is_none() {
+                    if let Some(e) = error_of(next.procs.0.get(&s)) {
+                        if failures.insert((s.clone(), format!("{}", e))) {
+                            ex.failures.push(Failure {
+                                schedule: schedule.clone(),
+                                proc: s.clone(),
+                                error: e.clone(),
+                            })
+                        }
+                    }
+                }
+                todo.push((next, schedule));
+            }
+        }
+        ex
+    }
+}
+
+/// Explore all interleavings of a system with the default settings.
+pub fn system(sys: &System) -> Exploration {
+    Explorer::default().explore(sys)
+}
+
+fn fmt_schedule(f: &mut fmt::Formatter, schedule: &[Sym]) -> fmt::Result {
+    write!(f, "[")?;
+    let mut i = schedule.iter().peekable();
+    while let Some(s) = i.next() {
+        write!(f, "{}", s)?;
+        if i.peek().is_some() {
+            write!(f, ", ")?;
+        }
+    }
+    write!(f, "]")
+}
+
+impl fmt::Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "explored {} states{}; {} final systems; {} failures",
+            self.states,
+            if self.truncated { " (truncated)" } else { "" },
+            self.finals.len(),
+            self.failures.len()
+        )?;
+        for (i, fin) in self.finals.iter().enumerate() {
+            write!(f, "final {} via ", i)?;
+            fmt_schedule(f, &fin.schedule)?;
+            writeln!(f, ":\n{}", fin.system)?;
+        }
+        for fail in self.failures.iter() {
+            write!(f, "failure of {}: {} via ", fail.proc, fail.error)?;
+            fmt_schedule(f, &fail.schedule)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Final stores of an exploration, deduplicated and sorted (for comparing outcomes).
+pub fn final_stores(ex: &Exploration) -> Vec<String> {
+    let mut stores: Vec<String> = ex
+        .finals
+        .iter()
+        .map(|fin| format!("{}", fin.system.store))
+        .collect();
+    stores.sort();
+    stores.dedup();
+    stores
+}
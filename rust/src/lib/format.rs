@@ -1,9 +1,9 @@
 #![allow(unused_imports)]
 use crate::ast::{
     step::{
-        Env, Error, ExtractError, Frame, FrameCont, Halted, InternalError, PatternError, Proc,
-        Procs, ProjectError, Running, Signal, Stack, Store, SwitchError, System, Trace, Traces,
-        ValsEnv, ValueError,
+        Env, Error, Event, ExtractError, Frame, FrameCont, Halted, InternalError, Limit,
+        PatternError, Proc, Procs, ProjectError, Running, Signal, Stack, Store, SwitchError,
+        System, Trace, Traces, ValsEnv, ValueError,
     },
     Branch, Branches, BxVal, BxesEnv, Case, Cases, Exp, FieldPat, FieldsPat, Pat, RecordVal, Sym,
     Val, ValField,
@@ -48,6 +48,7 @@ impl fmt::Display for Exp {
             Spawn(v, e) => write!(f, "~{} {{ {} }}", v, e),
             Put(v1, v2) => write!(f, "{} := {}", v1, v2),
             Get(v) => write!(f, "@{}", v),
+            GetWait(v) => write!(f, "@?{}", v),
             Link(v) => write!(f, "&{}", v),
             AssertEq(v1, true, v2) => write!(f, "{} == {}", v1, v2),
             AssertEq(v1, false, v2) => write!(f, "{} != {}", v1, v2),
@@ -62,6 +63,7 @@ impl fmt::Display for Exp {
             Project(e, v) => write!(f, "{} => {}", e, v),
             Extract(v) => write!(f, "{}", v),
             Hole => write!(f, "__"),
+            Loc(_, e) => write!(f, "{}", e),
         }
     }
 }
@@ -204,12 +206,30 @@ impl fmt::Display for Trace {
             }
             Ret(v) => write!(f, "ret {}", v),
             Put(s, v) => write!(f, "put {} <= {}", s, v),
+            Overwrite(s, v1, v2) => write!(f, "overwrite {}: {} => {}", s, v1, v2),
             Get(s, v) => write!(f, "get {} => {}", s, v),
+            Wait(s) => write!(f, "wait {}", s),
             Link(v1, v2) => write!(f, "link {} => {}", v1, v2),
         }
     }
 }
 
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Event::*;
+        match self {
+            NestBegin(s) => write!(f, "#{} {{", s),
+            NestEnd(s) => write!(f, "}} #{}", s),
+            Put(s, v) => write!(f, "put {} <= {}", s, v),
+            Get(s, v) => write!(f, "get {} => {}", s, v),
+            GetWait(s, v) => write!(f, "get-wait {} => {}", s, v),
+            Link(v1, v2) => write!(f, "link {} => {}", v1, v2),
+            Spawn(s) => write!(f, "spawn {}", s),
+            Halt(v) => write!(f, "halt {}", v),
+        }
+    }
+}
+
 impl fmt::Display for Proc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -316,8 +336,32 @@ impl fmt::Display for Error {
             NotLinkTarget(v) => write!(f, "notLinkTarget({})", v),
             Undefined(s) => write!(f, "undefined({})", s),
             Duplicate(s) => write!(f, "duplicate({})", s),
+            Overwrite(s, v1, v2) => write!(f, "overwrite({}, {} => {})", s, v1, v2),
             AssertionFailure(v1, true, v2) => write!(f, "assertionFailure({} == {})", v1, v2),
             AssertionFailure(v1, false, v2) => write!(f, "assertionFailure({} != {})", v1, v2),
+            OutOfFuel(l) => write!(f, "outOfFuel({})", l),
+        }
+    }
+}
+
+impl fmt::Display for crate::step::Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::step::Stop::*;
+        match self {
+            Quiescent => write!(f, "quiescent"),
+            NoProcs => write!(f, "noProcs"),
+            OutOfFuel(l) => write!(f, "outOfFuel({})", l),
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Limit::*;
+        match self {
+            Steps(n) => write!(f, "steps({})", n),
+            ProcSteps(n) => write!(f, "procSteps({})", n),
+            Time(d) => write!(f, "time({}ms)", d.as_millis()),
         }
     }
 }
@@ -444,3 +488,322 @@ impl fmt::Display for BxesEnv {
         write!(f, "]")
     }
 }
+
+impl fmt::Display for crate::memo::Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}/{:016x}", self.vars, self.reads)
+    }
+}
+
+/// One line per entry: nest, fingerprint, return value and puts.
+impl fmt::Display for crate::memo::Memo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (s, e) in self.entries().into_iter() {
+            write!(f, "{} {} => {}", s, e.fingerprint, e.retval)?;
+            let puts = e.puts();
+            if !puts.is_empty() {
+                write!(f, "; puts ")?;
+                for (i, (s, v)) in puts.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} := {}", s, v)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// # Source
+//
+// Unlike the `Display` forms above, which show running systems, source
+// parses back to the same program (up to locations), laid out with one
+// `let` per line and two-space indentation of multi-line blocks.
+//
+
+/// Source text of a program, as written by a person.
+pub fn source(e: &Exp) -> String {
+    let mut out = String::new();
+    src_exp(&mut out, 0, e);
+    out
+}
+
+fn pad(out: &mut String, indent: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(indent))
+}
+
+/// `{ body }` on one line, or over several when the body is.
+fn src_block(out: &mut String, indent: usize, e: &Exp) {
+    let mut body = String::new();
+    src_exp(&mut body, indent + 1, e);
+    if body.contains('\n') {
+        out.push('{');
+        pad(out, indent + 1);
+        out.push_str(&body);
+        pad(out, indent);
+        out.push('}')
+    } else {
+        out.push_str(&format!("{{ {} }}", body))
+    }
+}
+
+/// Entries between braces, on one line when there is one short entry.
+fn src_entries(out: &mut String, indent: usize, entries: Vec<String>) {
+    if entries.len() == 1 && !entries[0].contains('\n') {
+        out.push_str(&format!("{{ {} }}", entries[0]))
+    } else if entries.is_empty() {
+        out.push_str("{ }")
+    } else {
+        out.push('{');
+        for (i, entry) in entries.iter().enumerate() {
+            pad(out, indent + 1);
+            out.push_str(entry);
+            if i + 1 < entries.len() {
+                out.push(';')
+            }
+        }
+        pad(out, indent);
+        out.push('}')
+    }
+}
+
+/// An expression where the grammar expects an application head.
+fn src_head(out: &mut String, indent: usize, e: &Exp) {
+    match crate::step::unloc(e) {
+        Exp::App(_, _) | Exp::Project(_, _) | Exp::Branches(_) | Exp::Extract(Val::Var(_)) => {
+            src_exp(out, indent, e)
+        }
+        _ => {
+            out.push('(');
+            src_exp(out, indent, e);
+            out.push(')')
+        }
+    }
+}
+
+/// An expression before a `;`, parenthesized if it ends with one of its own.
+fn src_bound(out: &mut String, indent: usize, e: &Exp) {
+    match crate::step::unloc(e) {
+        Exp::Let(_, _, _) | Exp::LetBx(_, _, _) => {
+            out.push('(');
+            src_exp(out, indent, e);
+            out.push(')')
+        }
+        _ => src_exp(out, indent, e),
+    }
+}
+
+fn src_exp(out: &mut String, indent: usize, e: &Exp) {
+    use Exp::*;
+    match e {
+        Loc(_, e) => src_exp(out, indent, e),
+        Nest(v, body) | Spawn(v, body) => {
+            out.push(if let Nest(_, _) = e { '#' } else { '~' });
+            src_val(out, indent, v);
+            out.push(' ');
+            src_block(out, indent, body)
+        }
+        Put(v1, v2) => {
+            src_val(out, indent, v1);
+            out.push_str(" := ");
+            src_val(out, indent, v2)
+        }
+        Get(v) | GetWait(v) | Link(v) => {
+            out.push_str(match e {
+                Get(_) => "@",
+                GetWait(_) => "@?",
+                _ => "&",
+            });
+            src_val(out, indent, v)
+        }
+        AssertEq(v1, eq, v2) => {
+            out.push_str("assert ");
+            src_val(out, indent, v1);
+            out.push_str(if *eq { " == " } else { " != " });
+            src_val(out, indent, v2)
+        }
+        Lambda(p, e) => {
+            out.push_str(&format!("\\{} => ", src_pat(p)));
+            src_exp(out, indent, e)
+        }
+        App(head, v) | Project(head, v) => {
+            src_head(out, indent, head);
+            out.push_str(if let App(_, _) = e { " " } else { " <= " });
+            src_val(out, indent, v)
+        }
+        Let(p, e1, e2) => {
+            out.push_str(&format!("let {} = ", src_pat(p)));
+            src_bound(out, indent, e1);
+            out.push(';');
+            pad(out, indent);
+            src_exp(out, indent, e2)
+        }
+        LetBx(p, e1, e2) => {
+            match (p, crate::step::unloc(e1)) {
+                (Pat::Var(x), Ret(Val::Bx(bx))) if bx.name.is_none() => {
+                    out.push_str(&format!("box {} ", x));
+                    src_block(out, indent, &bx.code)
+                }
+                (Pat::Var(x), Ret(Val::Bx(bx))) if bx.name.as_ref() == Some(x) => {
+                    out.push_str(&format!("box rec {} ", x));
+                    src_block(out, indent, &bx.code)
+                }
+                _ => {
+                    out.push_str(&format!("let box {} = ", src_pat(p)));
+                    src_bound(out, indent, e1)
+                }
+            }
+            out.push(';');
+            pad(out, indent);
+            src_exp(out, indent, e2)
+        }
+        Ret(v) => {
+            out.push_str("ret ");
+            src_val(out, indent, v)
+        }
+        Switch(v, cs) => {
+            out.push_str("switch ");
+            src_val(out, indent, v);
+            out.push(' ');
+            let mut entries = vec![];
+            src_cases(&mut entries, indent + 1, cs);
+            src_entries(out, indent, entries)
+        }
+        Branches(bs) => {
+            let mut entries = vec![];
+            src_branches(&mut entries, indent + 1, bs);
+            src_entries(out, indent, entries)
+        }
+        Extract(Val::Var(x)) => out.push_str(x),
+        // Forms of running programs only.
+        Ret_(_) | Extract(_) | Hole => out.push_str(&e.to_string()),
+    }
+}
+
+fn src_cases(entries: &mut Vec<String>, indent: usize, cs: &Cases) {
+    match cs {
+        Cases::Empty => (),
+        Cases::Gather(cs1, cs2) => {
+            src_cases(entries, indent, cs1);
+            src_cases(entries, indent, cs2)
+        }
+        Cases::Case(c) => {
+            let mut out = "#".to_string();
+            src_val(&mut out, indent, &c.label);
+            out.push_str(&format!("({}) ", src_pat(&c.pattern)));
+            src_block(&mut out, indent, &c.body);
+            entries.push(out)
+        }
+    }
+}
+
+fn src_branches(entries: &mut Vec<String>, indent: usize, bs: &Branches) {
+    match bs {
+        Branches::Empty => (),
+        Branches::Gather(bs1, bs2) => {
+            src_branches(entries, indent, bs1);
+            src_branches(entries, indent, bs2)
+        }
+        Branches::Branch(b) => {
+            let mut out = String::new();
+            src_val(&mut out, indent, &b.label);
+            out.push_str(" =>");
+            let mut body = String::new();
+            src_exp(&mut body, indent + 1, &b.body);
+            if body.contains('\n') {
+                pad(&mut out, indent + 1)
+            } else {
+                out.push(' ')
+            }
+            out.push_str(&body);
+            entries.push(out)
+        }
+    }
+}
+
+fn src_pat(p: &Pat) -> String {
+    match p {
+        Pat::Ignore => "_".to_string(),
+        Pat::Var(x) => x.clone(),
+        // The parser gathers field patterns in reverse.
+        Pat::Fields(fps) => {
+            let fields: Vec<_> = fps
+                .0
+                .iter()
+                .rev()
+                .map(|fp| format!("{} => {}", src_val_string(&fp.label), src_pat(&fp.pattern)))
+                .collect();
+            format!("[{}]", fields.join("; "))
+        }
+        Pat::Case(fp) => format!("#{}({})", src_val_string(&fp.label), src_pat(&fp.pattern)),
+    }
+}
+
+fn src_val_string(v: &Val) -> String {
+    let mut out = String::new();
+    src_val(&mut out, 0, v);
+    out
+}
+
+fn src_val(out: &mut String, indent: usize, v: &Val) {
+    use Val::*;
+    match v {
+        CallByValue(e) => {
+            out.push_str("`(");
+            src_exp(out, indent, e);
+            out.push(')')
+        }
+        Sym(s) => out.push_str(&format!("${}", src_sym(s))),
+        Ptr(s) => out.push_str(&format!("!{}", src_sym(s))),
+        Proc(s) => out.push_str(&format!("~{}", src_sym(s))),
+        Var(x) => out.push_str(x),
+        Num(n) => out.push_str(&n.to_string()),
+        Variant(v1, v2) => {
+            out.push('#');
+            src_val(out, indent, v1);
+            out.push('(');
+            src_val(out, indent, v2);
+            out.push(')')
+        }
+        Record(RecordVal(fs)) => {
+            out.push('[');
+            for (i, f) in fs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str("; ")
+                }
+                src_val(out, indent, &f.label);
+                out.push_str(" => ");
+                src_val(out, indent, &f.value)
+            }
+            out.push(']')
+        }
+        Bx(bx) => {
+            if let Some(name) = &bx.name {
+                out.push_str(&format!("rec {} ", name))
+            }
+            src_block(out, indent, &bx.code)
+        }
+        RecordExt(_, _) => out.push_str(&v.to_string()),
+    }
+}
+
+fn src_sym(s: &Sym) -> String {
+    // A symbol before a suffix, which must not run into it.
+    let head = |s: &Sym| match s {
+        Sym::Num(_) | Sym::Id(_) => src_sym(s),
+        s => format!("({})", src_sym(s)),
+    };
+    match s {
+        Sym::Bin(s1, s2) => match (&**s1, &**s2) {
+            // `a_` would read as one identifier.
+            (Sym::Id(_), Sym::Under) => format!("({})_", src_sym(s1)),
+            _ => format!("{}{}", head(s1), s2),
+        },
+        Sym::Tri(s1, s2, s3) => format!("{}{}{}", head(s1), s2, src_sym(s3)),
+        s => s.to_string(),
+    }
+}
@@ -0,0 +1,147 @@
+//! Versioned store: the history of each store symbol.
+//!
+//! The store of a system keeps only the latest value of each symbol, but
+//! its timeline stamps every put (and every spawn, which puts the process
+//! name) with the writing process and the global step.  Replaying these
+//! gives every version of every symbol, from which we read the store as
+//! of any past step, and diff the store between two steps.
+//!
+//! Symbols that the host put in the store before the first step (see
+//! `System::seed`) have a first version at step 0, without a process.
+
+use crate::ast::{
+    step::{Event, Store, System},
+    Sym, Val,
+};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Value of a symbol, from the global step in which a process put it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub step: usize,
+    /// The writing process, or `None` for the host.
+    pub proc: Option<Sym>,
+    pub value: Val,
+}
+
+/// Change of a symbol between two versions of the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub sym: Sym,
+    pub before: Option<Val>,
+    pub after: Option<Val>,
+}
+
+/// Every version of every symbol, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History(pub BTreeMap<Sym, Vec<Version>>);
+
+impl History {
+    /// The history of the store of a system, from its timeline.
+    pub fn of(sys: &System) -> History {
+        let mut h: BTreeMap<Sym, Vec<Version>> = BTreeMap::new();
+        for (s, v) in sys.seed.0.iter() {
+            h.entry(s.clone()).or_default().push(Version {
+                step: 0,
+                proc: None,
+                value: v.clone(),
+            })
+        }
+        for st in sys.timeline.0.iter() {
+            let (s, value) = match &st.event {
+                Event::Put(s, v) => (s, v.clone()),
+                Event::Spawn(s) => (s, Val::Proc(s.clone())),
+                _ => continue,
+            };
+            h.entry(s.clone()).or_default().push(Version {
+                step: st.step,
+                proc: Some(st.proc.clone()),
+                value,
+            })
+        }
+        History(h)
+    }
+
+    /// The versions of a symbol, oldest first.
+    pub fn symbol(&self, s: &Sym) -> &[Version] {
+        self.0.get(s).map(|vs| vs.as_slice()).unwrap_or(&[])
+    }
+
+    /// The value of a symbol before the given global step, if any.
+    pub fn read(&self, s: &Sym, step: usize) -> Option<&Val> {
+        self.symbol(s)
+            .iter()
+            .rev()
+            .find(|v| v.step < step || v.proc.is_none())
+            .map(|v| &v.value)
+    }
+
+    /// The store as it was before the given global step.
+    /// As of the current step of the system, this is its store.
+    pub fn as_of(&self, step: usize) -> Store {
+        let mut store = Store(HashMap::new());
+        for s in self.0.keys() {
+            if let Some(v) = self.read(s, step) {
+                store.0.insert(s.clone(), v.clone());
+            }
+        }
+        store
+    }
+
+    /// The symbols whose values differ between the stores before two
+    /// global steps, in symbol order.
+    pub fn diff(&self, from: usize, to: usize) -> Vec<Change> {
+        self.0
+            .keys()
+            .filter_map(|s| {
+                let before = self.read(s, from);
+                let after = self.read(s, to);
+                if before == after {
+                    None
+                } else {
+                    Some(Change {
+                        sym: s.clone(),
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.proc {
+            Some(p) => write!(f, "step {} by {}: {}", self.step, p, self.value),
+            None => write!(f, "step {} by host: {}", self.step, self.value),
+        }
+    }
+}
+
+/// One line per change: `+` added, `-` removed, `~` changed.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (None, Some(v)) => write!(f, "+ {} => {}", self.sym, v),
+            (Some(v), None) => write!(f, "- {} => {}", self.sym, v),
+            (Some(v1), Some(v2)) => write!(f, "~ {}: {} => {}", self.sym, v1, v2),
+            (None, None) => write!(f, "  {}", self.sym),
+        }
+    }
+}
+
+/// Each symbol, followed by its versions, one per line.
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (s, vs) in self.0.iter() {
+            writeln!(f, "{}", s)?;
+            for v in vs.iter() {
+                writeln!(f, "  {}", v)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+//! Self-contained HTML trace explorer.
+//!
+//! Renders a (finished) system as a single static HTML page, with no
+//! external assets: one collapsible section per process, nests as
+//! collapsible blocks, and each `get` / `link` event linked to the
+//! `put` (or halting process) that produced the value it observed.
+
+use crate::ast::{
+    step::{FrameCont, Proc, Running, System, Trace},
+    Sym, Val,
+};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const STYLE: &str = "
+body { font-family: monospace; margin: 2em; }
+h1, h2 { font-family: sans-serif; }
+details { margin-left: 1em; }
+summary { cursor: pointer; }
+ol.trace { list-style: none; padding-left: 1em; margin: 0.2em 0; }
+.put { color: #1a7f37; }
+.overwrite { color: #bc4c00; }
+.get { color: #0550ae; }
+.link { color: #8250df; }
+.ret { color: #57606a; }
+.nest > summary { color: #953800; }
+.status-halted { color: #1a7f37; }
+.status-error { color: #cf222e; }
+.status-waiting { color: #9a6700; }
+.open { font-style: italic; }
+:target { background: #fff8c5; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #d0d7de; padding: 0.2em 0.6em; text-align: left; }
+";
+
+/// Escape text for inclusion in HTML content or attribute values.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Anchors for cross-linking events, assigned before rendering.
+struct Anchors {
+    /// Each process (in sorted order) gets a numbered section.
+    procs: HashMap<Sym, usize>,
+    /// Each put gets a numbered anchor; we keep (anchor, value) per symbol.
+    puts: HashMap<Sym, Vec<(usize, Val)>>,
+    next_put: usize,
+}
+
+impl Anchors {
+    fn new(sys: &System) -> Anchors {
+        let mut procs: Vec<_> = sys.procs.0.keys().cloned().collect();
+        procs.sort();
+        let mut anchors = Anchors {
+            procs: procs
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, s)| (s, i))
+                .collect(),
+            puts: HashMap::new(),
+            next_put: 0,
+        };
+        for s in procs.iter() {
+            for tr in crate::step::proc_traces(&sys.procs.0[s]) {
+                anchors.collect(tr)
+            }
+        }
+        anchors
+    }
+
+    fn collect(&mut self, tr: &Trace) {
+        match tr {
+            Trace::Seq(ts) | Trace::Nest(_, ts) => {
+                for t in ts.iter() {
+                    self.collect(t)
+                }
+            }
+            Trace::Put(s, v) => {
+                let a = self.next_put;
+                self.next_put += 1;
+                self.puts.entry(s.clone()).or_default().push((a, v.clone()));
+            }
+            Trace::Ret(_)
+            | Trace::Overwrite(_, _, _)
+            | Trace::Get(_, _)
+            | Trace::Wait(_)
+            | Trace::Link(_, _) => (),
+        }
+    }
+
+    /// The put that produced value `v` at symbol `s`, if any.
+    /// Prefers a put of an equal value; otherwise the last put of `s`.
+    fn put_of(&self, s: &Sym, v: Option<&Val>) -> Option<usize> {
+        let puts = self.puts.get(s)?;
+        if let Some(v) = v {
+            if let Some((a, _)) = puts.iter().rev().find(|(_, v0)| v0 == v) {
+                return Some(*a);
+            }
+        }
+        puts.last().map(|(a, _)| *a)
+    }
+}
+
+fn status(p: &Proc) -> (&'static str, String) {
+    match p {
+        Proc::Spawn(_) => ("running", "spawned".to_string()),
+        Proc::Running(_) => ("running", "running".to_string()),
+        Proc::WaitingForPtr(_, s) => ("waiting", format!("waiting for pointer {}", s)),
+        Proc::WaitingForHalt(_, s) => ("waiting", format!("waiting for process {}", s)),
+        Proc::Error(_, e) => ("error", format!("error: {}", e)),
+        Proc::Halted(h) => ("halted", format!("halted with {}", h.retval)),
+    }
+}
+
+struct Renderer<'a> {
+    anchors: &'a Anchors,
+    out: String,
+    /// Puts are numbered in the same order as `Anchors::collect` visits them.
+    next_put: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn proc_link(&self, s: &Sym, text: &str) -> String {
+        match self.anchors.procs.get(s) {
+            Some(i) => format!("<a href=\"#proc-{}\">{}</a>", i, escape(text)),
+            None => escape(text),
+        }
+    }
+
+    fn put_link(&self, s: &Sym, v: Option<&Val>, text: &str) -> String {
+        match self.anchors.put_of(s, v) {
+            Some(a) => format!("<a href=\"#put-{}\">{}</a>", a, escape(text)),
+            None => escape(text),
+        }
+    }
+
+    fn trace(&mut self, tr: &Trace) -> std::fmt::Result {
+        match tr {
+            Trace::Seq(ts) => {
+                for t in ts.iter() {
+                    self.trace(t)?
+                }
+            }
+            Trace::Nest(s, ts) => {
+                write!(
+                    self.out,
+                    "<li><details open class=\"nest\"><summary>#{}</summary><ol class=\"trace\">",
+                    escape(&s.to_string())
+                )?;
+                for t in ts.iter() {
+                    self.trace(t)?
+                }
+                write!(self.out, "</ol></details></li>")?;
+            }
+            Trace::Ret(v) => write!(
+                self.out,
+                "<li class=\"ret\">ret {}</li>",
+                escape(&v.to_string())
+            )?,
+            Trace::Put(s, v) => {
+                let a = self.next_put;
+                self.next_put += 1;
+                write!(
+                    self.out,
+                    "<li class=\"put\" id=\"put-{}\">put {} &lt;= {}</li>",
+                    a,
+                    escape(&s.to_string()),
+                    escape(&v.to_string())
+                )?
+            }
+            Trace::Overwrite(s, v1, v2) => {
+                let l = self.put_link(s, Some(v1), &s.to_string());
+                write!(
+                    self.out,
+                    "<li class=\"overwrite\">overwrite {}: {} =&gt; {}</li>",
+                    l,
+                    escape(&v1.to_string()),
+                    escape(&v2.to_string())
+                )?
+            }
+            Trace::Get(s, v) => {
+                let l = self.put_link(s, Some(v), &s.to_string());
+                write!(
+                    self.out,
+                    "<li class=\"get\">get {} =&gt; {}</li>",
+                    l,
+                    escape(&v.to_string())
+                )?
+            }
+            Trace::Wait(s) => {
+                let l = self.put_link(s, None, &s.to_string());
+                write!(self.out, "<li class=\"get\">wait {}</li>", l)?
+            }
+            Trace::Link(v1, v2) => {
+                let l = match v1 {
+                    Val::Sym(s) => self.put_link(s, None, &v1.to_string()),
+                    Val::Proc(s) => self.proc_link(s, &v1.to_string()),
+                    v1 => escape(&v1.to_string()),
+                };
+                write!(
+                    self.out,
+                    "<li class=\"link\">link {} =&gt; {}</li>",
+                    l,
+                    escape(&v2.to_string())
+                )?
+            }
+        };
+        Ok(())
+    }
+
+    fn running(&mut self, r: &Running) -> std::fmt::Result {
+        let mut open = 0;
+        for fr in r.stack.0.iter() {
+            for t in fr.trace.0.iter() {
+                self.trace(t)?
+            }
+            if let FrameCont::Nest(s) = &fr.cont {
+                write!(
+                    self.out,
+                    "<li><details open class=\"nest\"><summary>#{} <span class=\"open\">(unfinished)</span></summary><ol class=\"trace\">",
+                    escape(&s.to_string())
+                )?;
+                open += 1;
+            }
+        }
+        for t in r.trace.0.iter() {
+            self.trace(t)?
+        }
+        for _ in 0..open {
+            write!(self.out, "</ol></details></li>")?
+        }
+        write!(
+            self.out,
+            "<li class=\"open\">at {}</li>",
+            escape(&crate::step::head(&r.cont).to_string())
+        )
+    }
+
+    fn proc(&mut self, s: &Sym, p: &Proc) -> std::fmt::Result {
+        let (class, status) = status(p);
+        write!(
+            self.out,
+            "<details open class=\"proc\" id=\"proc-{}\"><summary>{} <span class=\"status-{}\">{}</span></summary><ol class=\"trace\">",
+            self.anchors.procs[s],
+            escape(&s.to_string()),
+            class,
+            escape(&status)
+        )?;
+        match p {
+            Proc::Spawn(e) => write!(
+                self.out,
+                "<li class=\"open\">at {}</li>",
+                escape(&crate::step::head(e).to_string())
+            )?,
+            Proc::Running(r)
+            | Proc::WaitingForPtr(r, _)
+            | Proc::WaitingForHalt(r, _)
+            | Proc::Error(r, _) => self.running(r)?,
+            Proc::Halted(h) => {
+                for t in h.trace.0.iter() {
+                    self.trace(t)?
+                }
+            }
+        }
+        write!(self.out, "</ol></details>")
+    }
+
+    fn store(&mut self, sys: &System) -> std::fmt::Result {
+        let mut xs: Vec<_> = sys.store.0.keys().collect();
+        xs.sort();
+        write!(self.out, "<table><tr><th>symbol</th><th>value</th></tr>")?;
+        for x in xs {
+            let v = &sys.store.0[x];
+            let l = match v {
+                Val::Proc(p) if p == x => self.proc_link(p, &x.to_string()),
+                v => self.put_link(x, Some(v), &x.to_string()),
+            };
+            write!(
+                self.out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                l,
+                escape(&v.to_string())
+            )?;
+        }
+        write!(self.out, "</table>")
+    }
+}
+
+/// Render a system as a self-contained HTML page.
+pub fn system(sys: &System) -> String {
+    let anchors = Anchors::new(sys);
+    let mut r = Renderer {
+        anchors: &anchors,
+        out: String::new(),
+        next_put: 0,
+    };
+    // Writing into a String cannot fail.
+    render(sys, &mut r).unwrap();
+    r.out
+}
+
+fn render(sys: &System, r: &mut Renderer) -> std::fmt::Result {
+    write!(
+        r.out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>fumola trace</title><style>{}</style></head><body>\n",
+        STYLE
+    )?;
+    write!(r.out, "<h1>fumola trace</h1>\n<h2>processes</h2>\n")?;
+    let mut ps: Vec<_> = sys.procs.0.keys().collect();
+    ps.sort();
+    for s in ps {
+        r.proc(s, &sys.procs.0[s])?;
+        writeln!(r.out)?;
+    }
+    writeln!(r.out, "<h2>store</h2>")?;
+    r.store(sys)?;
+    write!(r.out, "\n</body></html>\n")
+}
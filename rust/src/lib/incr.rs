@@ -0,0 +1,199 @@
+//! Incremental re-execution, by change propagation over nest traces.
+//!
+//! Each run records, for every nest that returns, what it read and wrote,
+//! in a memo table (see `memo`): its (fully nested) name, its body and the
+//! values of the body's free variables, its events, its trace and its
+//! return value.  Later in the same run, or on a later run (typically of
+//! the same program, with some store entries changed by the host), a
+//! process about to enter a recorded nest checks the record against the
+//! current store.  When every `Get` of the nest would read the value it
+//! read before, the nest is reused: its puts are applied to the store and
+//! its trace is spliced in, without stepping its body.  Otherwise, the nest
+//! runs again (and its own nests may still be reused).
+//!
+//! Nests that spawn processes or link to processes are never reused, nor
+//! are those with puts that the store policy forbids when replayed.  A
+//! reused nest takes no steps: its events enter the timeline all at the
+//! step that enters it, so step numbers differ from a from-scratch run,
+//! and with several processes racing on the store, schedules may
+//! interleave differently too.
+
+use crate::ast::{
+    step::{Env, Event, FrameCont, Proc, Stack, Stamp, System, Trace},
+    Exp, Sym, Val,
+};
+use crate::memo::{Memo, NestRun};
+use crate::observe::{Ignore, StepObserver};
+use crate::schedule::Scheduler;
+use crate::step::{fully_between, put_symbol, unloc, value, Fuel, Stop};
+
+use std::collections::HashMap;
+
+/// A nest still running, as recorded so far.
+#[derive(Debug, Clone)]
+struct Open {
+    key: Sym,
+    name: Sym,
+    env: Env,
+    body: Exp,
+    events: Vec<Event>,
+    reusable: bool,
+}
+
+/// Incremental engine, keeping nest records across runs.
+#[derive(Debug, Clone, Default)]
+pub struct Engine {
+    /// Nest records, by fully nested name.
+    pub cache: Memo,
+    /// Nests reused, over all runs.
+    pub reused: usize,
+    /// Nests run (and recorded), over all runs.
+    pub ran: usize,
+    open: HashMap<Sym, Vec<Open>>,
+    seen: usize,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    /// Fully step the system, like `step::fully_with`,
+    /// reusing recorded nests where the store permits it,
+    /// and recording the nests that run.
+    ///
+    /// The host supplies changed inputs by putting them in the store
+    /// of the system before running it.
+    pub fn run(&mut self, sys: &mut System, sched: &mut dyn Scheduler, fuel: &Fuel) -> Stop {
+        self.run_observed(sys, sched, fuel, &mut Ignore)
+    }
+
+    /// Fully step the system, like `run`,
+    /// reporting each process step to the observer.
+    pub fn run_observed(
+        &mut self,
+        sys: &mut System,
+        sched: &mut dyn Scheduler,
+        fuel: &Fuel,
+        obs: &mut dyn StepObserver,
+    ) -> Stop {
+        self.open.clear();
+        self.seen = sys.timeline.0.len();
+        let stop = fully_between(sys, sched, fuel, obs, &mut |sys| {
+            self.record(sys);
+            self.reuse(sys);
+        });
+        self.record(sys);
+        self.open.clear();
+        stop
+    }
+
+    /// Record the events of the timeline not yet seen.
+    fn record(&mut self, sys: &System) {
+        let stamps: Vec<Stamp> = sys.timeline.0[self.seen..].to_vec();
+        self.seen = sys.timeline.0.len();
+        for st in stamps.into_iter() {
+            let r = match sys.procs.0.get(&st.proc) {
+                Some(Proc::Running(r)) => Some(r),
+                _ => None,
+            };
+            match (&st.event, r) {
+                (Event::NestBegin(s), Some(r)) => {
+                    let outer = &r.stack.0[..r.stack.0.len().saturating_sub(1)];
+                    let key = put_symbol(&Stack(outer.to_vec()), s.clone());
+                    self.open.entry(st.proc.clone()).or_default().push(Open {
+                        key,
+                        name: s.clone(),
+                        env: r.env.clone(),
+                        body: r.cont.clone(),
+                        events: vec![],
+                        reusable: true,
+                    });
+                }
+                (Event::NestEnd(s), Some(r)) => {
+                    let open = match self.open.get_mut(&st.proc).and_then(|o| o.pop()) {
+                        Some(open) => open,
+                        None => continue,
+                    };
+                    let trace = match r.trace.0.last() {
+                        Some(Trace::Nest(s2, ts)) if s2 == s => ts.clone(),
+                        _ => continue,
+                    };
+                    self.ran += 1;
+                    if let (true, Exp::Ret_(v)) = (open.reusable && &open.name == s, &r.cont) {
+                        let run = NestRun::new(&open.env, open.body, open.events, trace, v.clone());
+                        self.cache.insert(open.key, run);
+                    }
+                }
+                (event, _) => {
+                    let reusable = !matches!(event, Event::Spawn(_) | Event::Link(Val::Proc(_), _));
+                    for open in self.open.entry(st.proc.clone()).or_default().iter_mut() {
+                        open.events.push(event.clone());
+                        open.reusable = open.reusable && reusable;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reuse the recorded nest that each running process is about to enter,
+    /// if its reads are unchanged.
+    fn reuse(&mut self, sys: &mut System) {
+        let mut procs: Vec<Sym> = sys.procs.0.keys().cloned().collect();
+        procs.sort();
+        for p in procs.into_iter() {
+            let r = match sys.procs.0.get_mut(&p) {
+                Some(Proc::Running(r)) => r,
+                _ => continue,
+            };
+            let (name, body) = match unloc(&r.cont) {
+                Exp::Nest(v, body) => match value(&r.env, v) {
+                    Ok(Val::Sym(s)) => (s, (**body).clone()),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let key = put_symbol(&r.stack, name.clone());
+            let run = match self.cache.lookup(&key, &r.env, &body, &sys.store) {
+                Some(run) => run,
+                None => continue,
+            };
+            let mut nests: Vec<Sym> = r
+                .stack
+                .0
+                .iter()
+                .filter_map(|fr| match &fr.cont {
+                    FrameCont::Nest(s) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect();
+            nests.push(name.clone());
+            let trace = match run.replay(&sys.store, &sys.policy, &mut nests) {
+                Some(trace) => trace,
+                None => continue,
+            };
+            let mut events = vec![Event::NestBegin(name.clone())];
+            events.extend(run.events.iter().cloned());
+            events.push(Event::NestEnd(name.clone()));
+            for e in run.events.iter() {
+                if let Event::Put(s, v) = e {
+                    sys.store.0.insert(s.clone(), v.clone());
+                }
+            }
+            r.trace.0.push(Trace::Nest(name, trace));
+            r.cont = Exp::Ret_(run.retval.clone());
+            for open in self.open.entry(p.clone()).or_default().iter_mut() {
+                open.events.extend(events.iter().cloned());
+            }
+            for event in events.into_iter() {
+                sys.timeline.0.push(Stamp {
+                    step: sys.step,
+                    proc: p.clone(),
+                    event,
+                })
+            }
+            self.seen = sys.timeline.0.len();
+            self.reused += 1;
+        }
+    }
+}
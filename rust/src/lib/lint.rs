@@ -0,0 +1,478 @@
+//! Lints for common mistakes.
+//!
+//! Each lint has a name (as given to `fumola lint --allow`) and a fixed
+//! severity: warnings flag code that is likely a mistake, and errors
+//! flag code that cannot do what it says (a case that never matches, a
+//! field that is never read).  A get of a symbol that the program never
+//! puts is only a warning, since the host may have put it in the store
+//! before the run.  A lint is allowed for the whole program, or at one
+//! line of it (`fumola lint --allow-at get-never-put@8`).
+//!
+//! Lints run over the parsed program, before `cbpv::convert`, so that
+//! they refer to the code as written; only `get-never-put` uses the
+//! effect analysis (see `effects`), which converts the program first.
+//! Scoping follows `scope`: box code sees no variables of its context.
+
+use crate::ast::{Branches, Case, Cases, Exp, Id, Loc, Pat, RecordVal, Sym, Val, ValField};
+use crate::cbpv::FreeVarsNoNext;
+use crate::effects::SiteKind;
+use crate::scope::VarKind;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Lint {
+    /// Variable bound by `let` and never used.
+    UnusedLet,
+    /// Box bound by `let box` (or `box`) and never extracted.
+    UnusedBox,
+    /// Variable bound where a variable of the same name is in scope.
+    Shadowed,
+    /// Switch case whose label never matches.
+    UnreachableCase,
+    /// Record field whose label an earlier field of the record has.
+    DuplicateLabel,
+    /// Get of a symbol that no put or spawn of the program uses.
+    GetNeverPut,
+    /// Assertion on two literal values, which always passes or always fails.
+    LiteralAssert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedLet,
+        Lint::UnusedBox,
+        Lint::Shadowed,
+        Lint::UnreachableCase,
+        Lint::DuplicateLabel,
+        Lint::GetNeverPut,
+        Lint::LiteralAssert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedLet => "unused-let",
+            Lint::UnusedBox => "unused-box",
+            Lint::Shadowed => "shadowed",
+            Lint::UnreachableCase => "unreachable-case",
+            Lint::DuplicateLabel => "duplicate-label",
+            Lint::GetNeverPut => "get-never-put",
+            Lint::LiteralAssert => "literal-assert",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Lint::UnusedLet
+            | Lint::UnusedBox
+            | Lint::Shadowed
+            | Lint::GetNeverPut
+            | Lint::LiteralAssert => Severity::Warning,
+            Lint::UnreachableCase | Lint::DuplicateLabel => Severity::Error,
+        }
+    }
+}
+
+impl std::str::FromStr for Lint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Lint, String> {
+        Lint::ALL
+            .iter()
+            .find(|l| l.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<_> = Lint::ALL.iter().map(|l| l.name()).collect();
+                format!("unknown lint {} (expected {})", s, names.join(", "))
+            })
+    }
+}
+
+/// A lint allowed at one line of the program, written `lint@line`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AllowAt {
+    pub lint: Lint,
+    pub line: usize,
+}
+
+impl std::str::FromStr for AllowAt {
+    type Err = String;
+    fn from_str(s: &str) -> Result<AllowAt, String> {
+        let (lint, line) = s
+            .split_once('@')
+            .ok_or_else(|| format!("expected lint@line, not {}", s))?;
+        Ok(AllowAt {
+            lint: lint.parse()?,
+            line: line
+                .parse()
+                .map_err(|_| format!("expected a line number, not {}", line))?,
+        })
+    }
+}
+
+/// A lint, where it applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub lint: Lint,
+    /// Innermost expression containing the code linted.
+    pub loc: Option<Loc>,
+    pub message: String,
+}
+
+struct Binder {
+    kind: VarKind,
+    name: Id,
+    loc: Option<Loc>,
+    used: bool,
+    /// Lint for never using the binder, if any.
+    unused: Option<Lint>,
+}
+
+#[derive(Default)]
+struct Linter {
+    findings: Vec<Finding>,
+    loc: Option<Loc>,
+    binders: Vec<Binder>,
+    /// First binder in scope; box code sees none of its context's.
+    floor: usize,
+}
+
+/// Label known from the program text.
+fn literal_label(v: &Val) -> Option<&Sym> {
+    match v {
+        Val::Sym(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// Location of a case body, or else `outer`.
+fn body_loc(c: &Case, outer: Option<Loc>) -> Option<Loc> {
+    match &*c.body {
+        Exp::Loc(l, _) => Some(*l),
+        _ => outer,
+    }
+}
+
+/// Whether a value is known from the program text alone.
+fn literal(v: &Val) -> bool {
+    match v {
+        Val::Num(_) | Val::Sym(_) | Val::Ptr(_) | Val::Proc(_) => true,
+        Val::Variant(v1, v2) => literal(v1) && literal(v2),
+        Val::Record(RecordVal(fs)) => fs.iter().all(|f| literal(&f.label) && literal(&f.value)),
+        _ => false,
+    }
+}
+
+fn flatten_cases<'a>(cs: &'a Cases, out: &mut Vec<&'a Case>) {
+    match cs {
+        Cases::Empty => (),
+        Cases::Gather(cs1, cs2) => {
+            flatten_cases(cs1, out);
+            flatten_cases(cs2, out)
+        }
+        Cases::Case(c) => out.push(c),
+    }
+}
+
+impl Linter {
+    fn finding(&mut self, lint: Lint, loc: Option<Loc>, message: String) {
+        self.findings.push(Finding { lint, loc, message })
+    }
+
+    fn bind(&mut self, kind: VarKind, name: &Id, unused: Option<Lint>) {
+        if self.binders[self.floor..]
+            .iter()
+            .any(|b| b.kind == kind && &b.name == name)
+        {
+            self.finding(
+                Lint::Shadowed,
+                self.loc,
+                format!("{} variable {} shadows an earlier binding", kind, name),
+            )
+        }
+        self.binders.push(Binder {
+            kind,
+            name: name.clone(),
+            loc: self.loc,
+            used: false,
+            unused,
+        })
+    }
+
+    fn bind_pat(&mut self, p: &Pat, unused: Option<Lint>) {
+        match p {
+            Pat::Ignore => (),
+            Pat::Var(x) => self.bind(VarKind::Val, x, unused),
+            Pat::Fields(fps) => fps
+                .0
+                .iter()
+                .for_each(|fp| self.bind_pat(&fp.pattern, unused)),
+            Pat::Case(fp) => self.bind_pat(&fp.pattern, unused),
+        }
+    }
+
+    /// Leave the scope of the binders after the first `mark`.
+    fn unbind(&mut self, mark: usize) {
+        for b in self.binders.split_off(mark) {
+            match b.unused {
+                Some(lint) if !b.used => {
+                    let what = match b.kind {
+                        VarKind::Val => "variable",
+                        VarKind::Box => "box",
+                    };
+                    self.finding(lint, b.loc, format!("{} {} is never used", what, b.name))
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn use_var(&mut self, kind: VarKind, name: &Id) {
+        if let Some(b) = self.binders[self.floor..]
+            .iter_mut()
+            .rev()
+            .find(|b| b.kind == kind && &b.name == name)
+        {
+            b.used = true
+        }
+    }
+
+    fn exp(&mut self, e: &Exp) {
+        use Exp::*;
+        match e {
+            Loc(l, e) => {
+                let outer = self.loc.replace(*l);
+                self.exp(e);
+                self.loc = outer
+            }
+            Hole => (),
+            Ret(v) | Ret_(v) | Get(v) | GetWait(v) | Link(v) => self.val(v),
+            Put(v1, v2) => {
+                self.val(v1);
+                self.val(v2)
+            }
+            AssertEq(v1, eq, v2) => {
+                if literal(v1) && literal(v2) {
+                    let passes = (v1 == v2) == *eq;
+                    self.finding(
+                        Lint::LiteralAssert,
+                        self.loc,
+                        format!(
+                            "assertion on literals always {}",
+                            if passes { "passes" } else { "fails" }
+                        ),
+                    )
+                }
+                self.val(v1);
+                self.val(v2)
+            }
+            Nest(v, e) | Spawn(v, e) => {
+                self.val(v);
+                self.exp(e)
+            }
+            App(e, v) | Project(e, v) => {
+                self.exp(e);
+                self.val(v)
+            }
+            Lambda(p, e) => {
+                let mark = self.binders.len();
+                self.bind_pat(p, None);
+                self.exp(e);
+                self.unbind(mark)
+            }
+            Let(p, e1, e2) => {
+                self.exp(e1);
+                let mark = self.binders.len();
+                self.bind_pat(p, Some(Lint::UnusedLet));
+                self.exp(e2);
+                self.unbind(mark)
+            }
+            LetBx(p, e1, e2) => {
+                self.exp(e1);
+                let mark = self.binders.len();
+                if let Pat::Var(x) = p {
+                    self.bind(VarKind::Box, x, Some(Lint::UnusedBox))
+                }
+                self.exp(e2);
+                self.unbind(mark)
+            }
+            Extract(Val::Var(x)) => self.use_var(VarKind::Box, x),
+            Extract(v) => self.val(v),
+            Switch(v, cs) => {
+                self.val(v);
+                self.cases(v, cs)
+            }
+            Branches(bs) => self.branches(bs),
+        }
+    }
+
+    fn cases(&mut self, v: &Val, cs: &Cases) {
+        let mut all = vec![];
+        flatten_cases(cs, &mut all);
+        // The label of the value switched on, when it is known.
+        let known = match v {
+            Val::Variant(l, _) if literal_label(l).is_some() => Some(&**l),
+            _ => None,
+        };
+        let mut seen: Vec<&Sym> = vec![];
+        for c in all {
+            if let Some(l) = literal_label(&c.label) {
+                let why = match known {
+                    _ if seen.contains(&l) => Some("an earlier case has its label".to_string()),
+                    Some(k) if k != &c.label => Some(format!("the value has label {}", k)),
+                    _ => None,
+                };
+                if let Some(why) = why {
+                    let loc = body_loc(c, self.loc);
+                    let m = format!("case #{} never matches: {}", c.label, why);
+                    self.finding(Lint::UnreachableCase, loc, m)
+                }
+                seen.push(l)
+            }
+            self.val(&c.label);
+            let mark = self.binders.len();
+            self.bind_pat(&c.pattern, None);
+            self.exp(&c.body);
+            self.unbind(mark)
+        }
+    }
+
+    fn branches(&mut self, bs: &Branches) {
+        match bs {
+            Branches::Empty => (),
+            Branches::Gather(bs1, bs2) => {
+                self.branches(bs1);
+                self.branches(bs2)
+            }
+            Branches::Branch(b) => {
+                self.val(&b.label);
+                self.exp(&b.body)
+            }
+        }
+    }
+
+    fn val(&mut self, v: &Val) {
+        use Val::*;
+        match v {
+            Var(x) => self.use_var(VarKind::Val, x),
+            CallByValue(e) => self.exp(e),
+            Bx(bx) => {
+                let floor = std::mem::replace(&mut self.floor, self.binders.len());
+                if let Some(name) = &bx.name {
+                    self.bind(VarKind::Val, name, None)
+                }
+                self.exp(&bx.code);
+                self.unbind(self.floor);
+                self.floor = floor
+            }
+            Record(RecordVal(fs)) => {
+                for (i, f) in fs.iter().enumerate() {
+                    if fs[..i]
+                        .iter()
+                        .any(|f2| literal(&f.label) && f2.label == f.label)
+                    {
+                        self.finding(
+                            Lint::DuplicateLabel,
+                            self.loc,
+                            format!(
+                                "field {} is never read: an earlier field has its label",
+                                f.label
+                            ),
+                        )
+                    }
+                }
+                fs.iter().for_each(|f| self.field(f))
+            }
+            RecordExt(v, f) => {
+                self.val(v);
+                self.field(f)
+            }
+            Variant(v1, v2) => {
+                self.val(v1);
+                self.val(v2)
+            }
+            Sym(_) | Ptr(_) | Proc(_) | Num(_) => (),
+        }
+    }
+
+    fn field(&mut self, f: &ValField) {
+        self.val(&f.label);
+        self.val(&f.value)
+    }
+}
+
+/// Findings of the lints not in `allow`, in source order.
+pub fn check(e: &Exp, allow: &BTreeSet<Lint>) -> Result<Vec<Finding>, FreeVarsNoNext> {
+    let mut l = Linter::default();
+    l.exp(e);
+    let mut findings = l.findings;
+    if !allow.contains(&Lint::GetNeverPut) {
+        let sites = crate::effects::sites(e)?;
+        let mut gets: Vec<&crate::effects::Site> = vec![];
+        for get in sites.iter().filter(|s| s.kind == SiteKind::Get) {
+            let put = sites
+                .iter()
+                .any(|s| s.kind != SiteKind::Get && s.sym.overlaps(&get.sym));
+            if !put && !gets.iter().any(|g| (g.loc, &g.sym) == (get.loc, &get.sym)) {
+                gets.push(get)
+            }
+        }
+        findings.extend(gets.into_iter().map(|g| Finding {
+            lint: Lint::GetNeverPut,
+            loc: g.loc,
+            message: format!("get of {}, which is never put", g.sym),
+        }))
+    }
+    findings.retain(|f| !allow.contains(&f.lint));
+    findings.sort_by_key(|f| f.loc.map(|l| l.start));
+    Ok(findings)
+}
+
+/// Drop the findings in `src` that some `AllowAt` allows at their line.
+pub fn allow_at(src: &str, findings: &mut Vec<Finding>, allow: &[AllowAt]) {
+    findings.retain(|f| match f.loc {
+        Some(l) => {
+            let (line, _) = crate::cover::line_col(src, l.start);
+            !allow.iter().any(|a| a.lint == f.lint && a.line == line)
+        }
+        None => true,
+    })
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// One `line:col: severity[lint]: message` line per finding in `src`.
+pub fn report(src: &str, findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(|f| {
+            let head = format!("{}[{}]: {}", f.lint.severity(), f.lint, f.message);
+            match f.loc {
+                Some(l) => {
+                    let (line, col) = crate::cover::line_col(src, l.start);
+                    format!("{}:{}: {}\n", line, col, head)
+                }
+                None => format!("{}\n", head),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,640 @@
+//! Language server, speaking the Language Server Protocol over stdio.
+//!
+//! Documents are synchronized in full on each change.  The server
+//! publishes diagnostics from the parser and from scope checking (see
+//! `scope`), and answers requests for definitions (of `let`, `box` and
+//! lambda binders), hovers (on symbols, with their puts, gets and spawns
+//! from `effects`, and on boxes, with their code), formatting (with
+//! `format::source`) and completion of the variables in scope.
+//!
+//! Positions are lines and characters, counting characters as UTF-16
+//! code units as the protocol does.
+
+use crate::ast::{Branches, Cases, Exp, Id, Loc, Pat, Val, ValField};
+use crate::effects::SymPat;
+use crate::scope::VarKind;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+//
+// # Positions
+//
+
+/// Protocol position (line and UTF-16 character) of a byte offset.
+pub fn position(src: &str, offset: usize) -> Value {
+    let before = &src[..offset.min(src.len())];
+    let line = before.matches('\n').count();
+    let start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character: usize = before[start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+/// Byte offset of a protocol position, clamped to its line.
+pub fn offset(src: &str, pos: &Value) -> usize {
+    let line = pos["line"].as_u64().unwrap_or(0) as usize;
+    let character = pos["character"].as_u64().unwrap_or(0) as usize;
+    let mut start = 0;
+    for _ in 0..line {
+        match src[start..].find('\n') {
+            Some(i) => start += i + 1,
+            None => return src.len(),
+        }
+    }
+    let mut units = 0;
+    for (i, c) in src[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16()
+    }
+    src.len()
+}
+
+fn range(src: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(src, start), "end": position(src, end) })
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Identifier (or symbol name) around a byte offset, and its span.
+fn word_at(src: &str, offset: usize) -> Option<(usize, usize)> {
+    let offset = offset.min(src.len());
+    let start = src[..offset]
+        .rfind(|c| !is_id_char(c))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let end = src[offset..]
+        .find(|c| !is_id_char(c))
+        .map(|i| offset + i)
+        .unwrap_or(src.len());
+    if start < end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// Sigil before a word, marking a symbol (`$`), pointer (`!`) or process (`~`).
+fn sigil(src: &str, start: usize) -> Option<char> {
+    src[..start].chars().last().filter(|c| "$!~".contains(*c))
+}
+
+/// First use of `name` as a variable in `src[start..end]`.
+fn find_var(src: &str, start: usize, end: usize, name: &str) -> Option<(usize, usize)> {
+    let end = end.min(src.len());
+    let mut from = start;
+    while let Some(i) = src[from..end].find(name) {
+        let (s, e) = (from + i, from + i + name.len());
+        let before = src[..s].chars().last();
+        let after = src[e..].chars().next();
+        if !before.is_some_and(is_id_char)
+            && !after.is_some_and(is_id_char)
+            && sigil(src, s).is_none()
+        {
+            return Some((s, e));
+        }
+        from = e
+    }
+    None
+}
+
+//
+// # Scopes
+//
+
+/// A binder of a variable in scope.
+#[derive(Debug, Clone)]
+pub struct Def {
+    pub kind: VarKind,
+    pub name: Id,
+    /// Span of the bound name, or else of the binding expression.
+    pub span: (usize, usize),
+    /// Code of a box bound by `let box` or `box`.
+    pub code: Option<Exp>,
+}
+
+/// Scopes of a program's expressions, as in `scope`, keeping the
+/// innermost expression around an offset.
+struct Scopes<'a> {
+    src: &'a str,
+    offset: usize,
+    defs: Vec<Def>,
+    /// First binder in scope; box code sees none of its context's.
+    floor: usize,
+    loc: Option<Loc>,
+    /// Innermost expression around the offset, and the binders in scope there.
+    found: Option<(&'a Exp, Vec<Def>)>,
+}
+
+impl<'a> Scopes<'a> {
+    fn def(&mut self, kind: VarKind, name: &Id, code: Option<Exp>) {
+        let (start, end) = match self.loc {
+            Some(l) => (l.start, l.end),
+            None => (0, self.src.len()),
+        };
+        let span = find_var(self.src, start, end, name).unwrap_or((start, start));
+        self.defs.push(Def {
+            kind,
+            name: name.clone(),
+            span,
+            code,
+        })
+    }
+
+    fn pattern(&mut self, p: &Pat) {
+        match p {
+            Pat::Ignore => (),
+            Pat::Var(x) => self.def(VarKind::Val, x, None),
+            Pat::Fields(fps) => fps.0.iter().for_each(|fp| self.pattern(&fp.pattern)),
+            Pat::Case(fp) => self.pattern(&fp.pattern),
+        }
+    }
+
+    fn exp(&mut self, e: &'a Exp) {
+        use Exp::*;
+        match e {
+            Loc(l, body) => {
+                if l.start <= self.offset && self.offset <= l.end {
+                    self.found = Some((body, self.defs[self.floor..].to_vec()))
+                }
+                let outer = self.loc.replace(*l);
+                self.exp(body);
+                self.loc = outer
+            }
+            Hole => (),
+            Ret(v) | Ret_(v) | Get(v) | GetWait(v) | Link(v) | Extract(v) => self.val(v),
+            Put(v1, v2) | AssertEq(v1, _, v2) => {
+                self.val(v1);
+                self.val(v2)
+            }
+            Nest(v, e) | Spawn(v, e) => {
+                self.val(v);
+                self.exp(e)
+            }
+            App(e, v) | Project(e, v) => {
+                self.exp(e);
+                self.val(v)
+            }
+            Lambda(p, e) => {
+                let mark = self.defs.len();
+                self.pattern(p);
+                self.exp(e);
+                self.defs.truncate(mark)
+            }
+            Let(p, e1, e2) => {
+                self.exp(e1);
+                let mark = self.defs.len();
+                self.pattern(p);
+                self.exp(e2);
+                self.defs.truncate(mark)
+            }
+            LetBx(p, e1, e2) => {
+                self.exp(e1);
+                let mark = self.defs.len();
+                if let Pat::Var(x) = p {
+                    let code = match crate::step::unloc(e1) {
+                        Ret(Val::Bx(bx)) => Some(bx.code.clone()),
+                        _ => None,
+                    };
+                    self.def(VarKind::Box, x, code)
+                }
+                self.exp(e2);
+                self.defs.truncate(mark)
+            }
+            Switch(v, cs) => {
+                self.val(v);
+                self.cases(cs)
+            }
+            Branches(bs) => self.branches(bs),
+        }
+    }
+
+    fn cases(&mut self, cs: &'a Cases) {
+        match cs {
+            Cases::Empty => (),
+            Cases::Gather(cs1, cs2) => {
+                self.cases(cs1);
+                self.cases(cs2)
+            }
+            Cases::Case(c) => {
+                self.val(&c.label);
+                let mark = self.defs.len();
+                self.pattern(&c.pattern);
+                self.exp(&c.body);
+                self.defs.truncate(mark)
+            }
+        }
+    }
+
+    fn branches(&mut self, bs: &'a Branches) {
+        match bs {
+            Branches::Empty => (),
+            Branches::Gather(bs1, bs2) => {
+                self.branches(bs1);
+                self.branches(bs2)
+            }
+            Branches::Branch(b) => {
+                self.val(&b.label);
+                self.exp(&b.body)
+            }
+        }
+    }
+
+    fn val(&mut self, v: &'a Val) {
+        use Val::*;
+        match v {
+            CallByValue(e) => self.exp(e),
+            Bx(bx) => {
+                let floor = std::mem::replace(&mut self.floor, self.defs.len());
+                if let Some(name) = &bx.name {
+                    self.def(VarKind::Val, name, None)
+                }
+                self.exp(&bx.code);
+                self.defs.truncate(self.floor);
+                self.floor = floor
+            }
+            Record(r) => r.0.iter().for_each(|f| self.field(f)),
+            RecordExt(v, f) => {
+                self.val(v);
+                self.field(f)
+            }
+            Variant(v1, v2) => {
+                self.val(v1);
+                self.val(v2)
+            }
+            Var(_) | Sym(_) | Ptr(_) | Proc(_) | Num(_) => (),
+        }
+    }
+
+    fn field(&mut self, f: &'a ValField) {
+        self.val(&f.label);
+        self.val(&f.value)
+    }
+}
+
+/// Innermost expression around `offset`, and the variables in scope there
+/// (innermost last).
+fn scope_at<'a>(src: &'a str, e: &'a Exp, offset: usize) -> Option<(&'a Exp, Vec<Def>)> {
+    let mut s = Scopes {
+        src,
+        offset,
+        defs: vec![],
+        floor: 0,
+        loc: None,
+        found: None,
+    };
+    s.exp(e);
+    s.found
+}
+
+/// Whether `e` applies (or projects from) the box `name`.
+fn extracts(e: &Exp, name: &str) -> bool {
+    match e {
+        Exp::App(e, _) | Exp::Project(e, _) | Exp::Loc(_, e) => extracts(e, name),
+        Exp::Extract(Val::Var(x)) => x == name,
+        _ => false,
+    }
+}
+
+/// Binder of the variable at `offset`.
+pub fn definition(src: &str, e: &Exp, offset: usize) -> Option<Def> {
+    let (start, end) = word_at(src, offset)?;
+    if sigil(src, start).is_some() {
+        return None;
+    }
+    let name = &src[start..end];
+    let (inner, defs) = scope_at(src, e, offset)?;
+    let kinds = if extracts(inner, name) {
+        [VarKind::Box, VarKind::Val]
+    } else {
+        [VarKind::Val, VarKind::Box]
+    };
+    kinds.iter().find_map(|k| {
+        defs.iter()
+            .rev()
+            .find(|d| d.kind == *k && d.name == name)
+            .cloned()
+    })
+}
+
+/// Variables in scope at `offset`, innermost first, each name once.
+pub fn completions(src: &str, e: &Exp, offset: usize) -> Vec<Def> {
+    let mut out: Vec<Def> = vec![];
+    if let Some((_, defs)) = scope_at(src, e, offset) {
+        for d in defs.into_iter().rev() {
+            if !out.iter().any(|d2| d2.kind == d.kind && d2.name == d.name) {
+                out.push(d)
+            }
+        }
+    }
+    out
+}
+
+fn sym_tail(p: &SymPat, s: &crate::ast::Sym) -> bool {
+    match p {
+        SymPat::Sym(s2) => s2 == s,
+        SymPat::Nest(_, p) => sym_tail(p, s),
+        SymPat::Any => false,
+    }
+}
+
+/// Markdown describing the symbol or box at `offset`.
+pub fn hover(src: &str, e: &Exp, offset: usize) -> Option<String> {
+    let (start, end) = word_at(src, offset)?;
+    if sigil(src, start).is_some() {
+        // Symbols may extend over several words (`$a.b`), so take the
+        // innermost symbol that the parser reads here.
+        let mut end = end;
+        let mut sym = None;
+        while end <= src.len() {
+            match crate::parser::SymParser::new().parse(&src[start..end]) {
+                Ok(s) => sym = Some(s),
+                Err(_) if sym.is_some() => break,
+                Err(_) => (),
+            }
+            end += src[end..].chars().next().map_or(1, char::len_utf8);
+        }
+        let sym = sym?;
+        let mut text = format!("symbol `${}`", sym);
+        if let Ok(sites) = crate::effects::sites(e) {
+            let mut lines: Vec<String> = vec![];
+            for site in sites.iter().filter(|s| sym_tail(&s.sym, &sym)) {
+                let at = match site.loc {
+                    Some(l) => {
+                        let (line, col) = crate::cover::line_col(src, l.start);
+                        format!("{}:{}", line, col)
+                    }
+                    None => "?".to_string(),
+                };
+                let line = format!("- {} `{}` at {}", site.kind, site.sym, at);
+                if !lines.contains(&line) {
+                    lines.push(line)
+                }
+            }
+            if !lines.is_empty() {
+                text.push_str("\n\n");
+                text.push_str(&lines.join("\n"))
+            }
+        }
+        return Some(text);
+    }
+    let d = definition(src, e, offset)?;
+    match (d.kind, d.code) {
+        (VarKind::Box, Some(code)) => Some(format!(
+            "```\nbox {} {{ {} }}\n```",
+            d.name,
+            crate::format::source(&code)
+        )),
+        (VarKind::Box, None) => Some(format!("box `{}`", d.name)),
+        (VarKind::Val, _) => None,
+    }
+}
+
+/// Diagnostics of a document: its parse error, or its unbound variables.
+pub fn diagnostics(src: &str) -> Vec<Value> {
+    use lalrpop_util::ParseError::*;
+    let e = match crate::parser::ExpParser::new().parse(src) {
+        Ok(e) => e,
+        Err(err) => {
+            let (start, end) = match &err {
+                InvalidToken { location } => (*location, *location),
+                UnrecognizedEOF { location, .. } => (*location, *location),
+                UnrecognizedToken { token, .. } | ExtraToken { token } => (token.0, token.2),
+                User { .. } => (0, 0),
+            };
+            return vec![json!({
+                "range": range(src, start, end),
+                "severity": 1,
+                "source": "fumola",
+                "message": err.to_string(),
+            })];
+        }
+    };
+    crate::scope::check(&e)
+        .iter()
+        .map(|u| {
+            let (start, end) = match u.loc {
+                Some(l) => find_var(src, l.start, l.end, &u.name).unwrap_or((l.start, l.end)),
+                None => (0, 0),
+            };
+            json!({
+                "range": range(src, start, end),
+                "severity": 1,
+                "source": "fumola",
+                "message": u.to_string(),
+            })
+        })
+        .collect()
+}
+
+//
+// # Protocol
+//
+
+/// Read one message, or `None` at the end of input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok()
+            }
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(out: &mut dyn Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+#[derive(Default)]
+struct Document {
+    text: String,
+    /// Last text that parsed, and its program, for completing in text
+    /// being edited.
+    parsed: Option<(String, Exp)>,
+}
+
+#[derive(Default)]
+pub struct Server {
+    docs: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    fn open(&mut self, uri: &str, text: String, out: &mut dyn Write) -> io::Result<()> {
+        let doc = self.docs.entry(uri.to_string()).or_default();
+        if let Ok(e) = crate::parser::ExpParser::new().parse(&text) {
+            doc.parsed = Some((text.clone(), e))
+        }
+        let diagnostics = diagnostics(&text);
+        doc.text = text;
+        write_message(
+            out,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics },
+            }),
+        )
+    }
+
+    /// Current text and program of a document, if it parses.
+    fn program(&self, uri: &str) -> Option<(&str, Exp)> {
+        let doc = self.docs.get(uri)?;
+        let e = crate::parser::ExpParser::new().parse(&doc.text).ok()?;
+        Some((&doc.text, e))
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let at = |src: &str| offset(src, &params["position"]);
+        Ok(match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentFormattingProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "fumola" },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/definition" => match self.program(uri) {
+                Some((src, e)) => match definition(src, &e, at(src)) {
+                    Some(d) => json!({ "uri": uri, "range": range(src, d.span.0, d.span.1) }),
+                    None => Value::Null,
+                },
+                None => Value::Null,
+            },
+            "textDocument/hover" => match self.program(uri) {
+                Some((src, e)) => match hover(src, &e, at(src)) {
+                    Some(text) => json!({ "contents": { "kind": "markdown", "value": text } }),
+                    None => Value::Null,
+                },
+                None => Value::Null,
+            },
+            "textDocument/formatting" => match self.program(uri) {
+                Some((src, e)) => {
+                    let text = format!("{}\n", crate::format::source(&e));
+                    json!([{ "range": range(src, 0, src.len()), "newText": text }])
+                }
+                None => Value::Null,
+            },
+            "textDocument/completion" => {
+                let doc = self.docs.get(uri);
+                let items = match doc.and_then(|d| d.parsed.as_ref().map(|p| (d, p))) {
+                    Some((doc, (src, e))) => {
+                        // Offsets in the last text that parsed, for edits since.
+                        let at = at(&doc.text).min(src.len());
+                        completions(src, e, at)
+                            .into_iter()
+                            .map(|d| {
+                                let kind = match d.kind {
+                                    VarKind::Val => 6,
+                                    VarKind::Box => 3,
+                                };
+                                json!({ "label": d.name, "kind": kind, "detail": d.kind.to_string() })
+                            })
+                            .collect()
+                    }
+                    None => vec![],
+                };
+                Value::Array(items)
+            }
+            _ => return Err((-32601, format!("unknown method {}", method))),
+        })
+    }
+
+    /// Handle one message, returning whether to go on.
+    pub fn message(&mut self, msg: &Value, out: &mut dyn Write) -> io::Result<bool> {
+        let method = msg["method"].as_str().unwrap_or("");
+        let params = &msg["params"];
+        match (msg.get("id"), method) {
+            (_, "exit") => return Ok(false),
+            (Some(id), _) => {
+                let reply = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                write_message(out, &reply)?
+            }
+            (None, "textDocument/didOpen") => {
+                let doc = &params["textDocument"];
+                let text = doc["text"].as_str().unwrap_or("").to_string();
+                self.open(doc["uri"].as_str().unwrap_or(""), text, out)?
+            }
+            (None, "textDocument/didChange") => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let changes = params["contentChanges"].as_array();
+                if let Some(change) = changes.and_then(|cs| cs.last()) {
+                    let text = change["text"].as_str().unwrap_or("").to_string();
+                    self.open(uri, text, out)?
+                }
+            }
+            (None, "textDocument/didClose") => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                self.docs.remove(uri);
+                write_message(
+                    out,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": { "uri": uri, "diagnostics": [] },
+                    }),
+                )?
+            }
+            // Other notifications (`initialized`, `$/cancelRequest`, ...).
+            (None, _) => (),
+        }
+        Ok(true)
+    }
+
+    /// Serve messages until `exit` or end of input.
+    pub fn session<R: BufRead>(&mut self, mut input: R, out: &mut dyn Write) -> io::Result<()> {
+        while let Some(msg) = read_message(&mut input)? {
+            if !self.message(&msg, out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a client asked to shut down before exiting.
+    pub fn shut_down(&self) -> bool {
+        self.shutdown
+    }
+}
@@ -0,0 +1,249 @@
+//! Memo table of named nests.
+//!
+//! A nest is keyed by its fully nested name (see `step::put_symbol`), and
+//! each of its entries records one way it ran: its body, the values of the
+//! body's free variables, its events (including its store reads), its trace
+//! and its return value.  An entry applies again when the nest starts with
+//! the same body and free variables, and each of its gets would read the
+//! same value from the store as before.  Then the nest's puts can be
+//! replayed instead of stepping its body (see `incr::Engine`).
+//!
+//! Replaying an entry records the overwrites that its puts make in the
+//! current store, which may differ from those of its trace, and fails if
+//! the store policy forbids one of them.
+//!
+//! Entries carry a fingerprint, hashing their free variables and their
+//! reads, which tells apart the entries of one nest when inspecting them.
+
+use crate::ast::{
+    step::{Env, Event, Store, StorePolicy, Trace, ValsEnv},
+    BxesEnv, Exp, Sym, Val,
+};
+use crate::scope::{free, VarKind};
+use crate::step::{nest_policy, overwrites};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Hashes of the free variables of a nest, and of what it read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint {
+    pub vars: u64,
+    pub reads: u64,
+}
+
+/// What a nest did when it ran.
+#[derive(Debug, Clone)]
+pub struct NestRun {
+    pub fingerprint: Fingerprint,
+    /// The environment, restricted to the free variables of the body.
+    pub env: Env,
+    pub body: Exp,
+    /// Events of the nest, not including its own begin and end.
+    pub events: Vec<Event>,
+    pub trace: Vec<Trace>,
+    pub retval: Val,
+}
+
+impl NestRun {
+    /// Record a nest that ran in `env`, which need not be restricted.
+    pub fn new(env: &Env, body: Exp, events: Vec<Event>, trace: Vec<Trace>, retval: Val) -> Self {
+        let env = restrict(env, &body);
+        NestRun {
+            fingerprint: Fingerprint {
+                vars: vars(&env),
+                reads: reads(&events),
+            },
+            env,
+            body,
+            events,
+            trace,
+            retval,
+        }
+    }
+
+    /// The trace of the nest replayed on the store, within the given nests
+    /// (outermost first, ending with its own name), with the overwrites of
+    /// its puts there; `None` if the store policy forbids one of them.
+    pub fn replay(
+        &self,
+        store: &Store,
+        policy: &StorePolicy,
+        nests: &mut Vec<Sym>,
+    ) -> Option<Vec<Trace>> {
+        retrace(&self.trace, store, policy, nests, &mut HashMap::new())
+    }
+
+    /// The symbols put by the nest, each with its last value.
+    pub fn puts(&self) -> Vec<(&Sym, &Val)> {
+        let mut puts: Vec<(&Sym, &Val)> = vec![];
+        for e in self.events.iter() {
+            if let Event::Put(s, v) = e {
+                puts.retain(|(s2, _)| *s2 != s);
+                puts.push((s, v))
+            }
+        }
+        puts
+    }
+}
+
+/// Recorded runs of nests, by fully nested name.
+#[derive(Debug, Clone, Default)]
+pub struct Memo {
+    table: HashMap<Sym, Vec<NestRun>>,
+}
+
+impl Memo {
+    pub fn new() -> Memo {
+        Memo::default()
+    }
+
+    /// Number of entries, over all nests.
+    pub fn len(&self) -> usize {
+        self.table.values().map(|es| es.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear()
+    }
+
+    /// Does the table hold an entry for this nest?
+    pub fn contains_key(&self, key: &Sym) -> bool {
+        self.table.contains_key(key)
+    }
+
+    /// Every entry, by nest name and then by fingerprint.
+    pub fn entries(&self) -> Vec<(&Sym, &NestRun)> {
+        let mut es: Vec<(&Sym, &NestRun)> = self
+            .table
+            .iter()
+            .flat_map(|(s, es)| es.iter().map(move |e| (s, e)))
+            .collect();
+        es.sort_by(|(s1, e1), (s2, e2)| (s1, e1.fingerprint).cmp(&(s2, e2.fingerprint)));
+        es
+    }
+
+    /// Add an entry, replacing any that ran the same way.
+    pub fn insert(&mut self, key: Sym, run: NestRun) {
+        let es = self.table.entry(key).or_default();
+        es.retain(|e| e.fingerprint != run.fingerprint || e.body != run.body);
+        es.push(run)
+    }
+
+    /// The entry that applies to the nest about to run `body` in `env`,
+    /// given the current store, if any.
+    pub fn lookup(&self, key: &Sym, env: &Env, body: &Exp, store: &Store) -> Option<&NestRun> {
+        let es = self.table.get(key)?;
+        let env = restrict(env, body);
+        let fp = vars(&env);
+        es.iter().find(|e| {
+            e.fingerprint.vars == fp && e.body == *body && e.env == env && valid(store, &e.events)
+        })
+    }
+}
+
+fn retrace(
+    ts: &[Trace],
+    store: &Store,
+    policy: &StorePolicy,
+    nests: &mut Vec<Sym>,
+    puts: &mut HashMap<Sym, Val>,
+) -> Option<Vec<Trace>> {
+    let mut out = vec![];
+    for t in ts.iter() {
+        match t {
+            // Replayed gets find their symbols, without waiting.
+            Trace::Overwrite(_, _, _) | Trace::Wait(_) => (),
+            Trace::Put(s, v) => {
+                if let Some(v0) = puts.get(s).or_else(|| store.0.get(s)) {
+                    if !overwrites(nest_policy(policy, nests.iter()), v0, v) {
+                        return None;
+                    }
+                    out.push(Trace::Overwrite(s.clone(), v0.clone(), v.clone()))
+                }
+                puts.insert(s.clone(), v.clone());
+                out.push(t.clone())
+            }
+            Trace::Nest(n, ts) => {
+                nests.push(n.clone());
+                let ts = retrace(ts, store, policy, nests, puts);
+                nests.pop();
+                out.push(Trace::Nest(n.clone(), ts?))
+            }
+            Trace::Seq(ts) => out.push(Trace::Seq(retrace(ts, store, policy, nests, puts)?)),
+            Trace::Ret(_) | Trace::Get(_, _) | Trace::Link(_, _) => out.push(t.clone()),
+        }
+    }
+    Some(out)
+}
+
+/// The environment of `body`, restricted to its free variables.
+pub fn restrict(env: &Env, body: &Exp) -> Env {
+    let mut r = Env {
+        vals: ValsEnv(HashMap::new()),
+        bxes: BxesEnv(HashMap::new()),
+    };
+    for (kind, x) in free(body).into_iter() {
+        match kind {
+            VarKind::Val => {
+                if let Some(v) = env.vals.0.get(&x) {
+                    r.vals.0.insert(x, v.clone());
+                }
+            }
+            VarKind::Box => {
+                if let Some(bx) = env.bxes.0.get(&x) {
+                    r.bxes.0.insert(x, bx.clone());
+                }
+            }
+        }
+    }
+    r
+}
+
+fn vars(env: &Env) -> u64 {
+    let mut h = DefaultHasher::new();
+    format!("{}", env.vals).hash(&mut h);
+    format!("{}", env.bxes).hash(&mut h);
+    h.finish()
+}
+
+fn reads(events: &[Event]) -> u64 {
+    let mut h = DefaultHasher::new();
+    for e in events.iter() {
+        if let Event::Get(_, _) | Event::GetWait(_, _) | Event::Link(_, _) = e {
+            format!("{}", e).hash(&mut h)
+        }
+    }
+    h.finish()
+}
+
+/// Would the recorded events happen again, in the given store?
+/// Each get must read the same value (seeing earlier puts of the nest),
+/// and each linked symbol must be defined.
+fn valid(store: &Store, events: &[Event]) -> bool {
+    let mut puts: HashMap<&Sym, &Val> = HashMap::new();
+    for e in events.iter() {
+        let read = |s: &Sym| puts.get(s).cloned().or_else(|| store.0.get(s));
+        match e {
+            Event::Put(s, v) => drop(puts.insert(s, v)),
+            Event::Get(s, v) | Event::GetWait(s, v) => {
+                if read(s) != Some(v) {
+                    return false;
+                }
+            }
+            Event::Link(Val::Sym(s), _) => {
+                if read(s).is_none() {
+                    return false;
+                }
+            }
+            Event::NestBegin(_) | Event::NestEnd(_) => (),
+            Event::Link(_, _) | Event::Spawn(_) | Event::Halt(_) => return false,
+        }
+    }
+    true
+}
@@ -1,7 +1,27 @@
 pub mod ast;
 pub mod cbpv;
 pub mod check;
+pub mod chrome;
+pub mod cover;
+pub mod debug;
+pub mod effects;
+pub mod explore;
 pub mod format;
+pub mod history;
+pub mod html;
+pub mod incr;
+pub mod lint;
+pub mod lsp;
+pub mod memo;
+pub mod observe;
 #[allow(clippy::all)]
 pub mod parser;
+pub mod profile;
+pub mod quiesce;
+pub mod race;
+pub mod repl;
+pub mod schedule;
+pub mod scope;
 pub mod step;
+pub mod types;
+pub mod unique;
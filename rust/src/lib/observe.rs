@@ -0,0 +1,158 @@
+//! Observing the stepper.
+//!
+//! `step::system_observed` and `step::fully_observed` report each process
+//! step to a `StepObserver`, as typed events: where the process stepped
+//! from, what it put, got, linked, spawned or returned, and whether it
+//! failed or blocked.
+
+use crate::ast::{
+    step::{Error, Event},
+    Exp, Sym,
+};
+
+use std::fmt;
+use std::io::Write;
+
+/// What happened to one process in one system step.
+#[derive(Debug, Clone)]
+pub struct StepEvent {
+    pub step: usize,
+    pub proc: Sym,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+pub enum Kind {
+    /// The process stepped, from this expression (shallow, see `step::head`),
+    /// with this many stack frames and env entries (vals and boxes).
+    Stepped { at: Exp, depth: usize, env: usize },
+    /// A put, get, resolved link, spawn, halt or nest boundary
+    /// (as recorded in the timeline).
+    Event(Event),
+    /// The process failed.
+    Error(Error),
+    /// The process blocked, waiting for a pointer to this symbol.
+    WaitPtr(Sym),
+    /// The process blocked, waiting for this process to halt.
+    WaitHalt(Sym),
+}
+
+pub trait StepObserver {
+    fn observe(&mut self, event: &StepEvent);
+}
+
+/// Observe nothing.
+pub struct Ignore;
+
+impl StepObserver for Ignore {
+    fn observe(&mut self, _event: &StepEvent) {}
+}
+
+/// Log events through the `log` crate: steps at trace level, failures at
+/// info level, and everything else at debug level.
+pub struct Log;
+
+impl StepObserver for Log {
+    fn observe(&mut self, event: &StepEvent) {
+        match event.kind {
+            Kind::Stepped { .. } => log::trace!("{}", event),
+            Kind::Error(_) => log::info!("{}", event),
+            _ => log::debug!("{}", event),
+        }
+    }
+}
+
+/// Collect events, in order.
+#[derive(Debug, Clone, Default)]
+pub struct Collect(pub Vec<StepEvent>);
+
+impl StepObserver for Collect {
+    fn observe(&mut self, event: &StepEvent) {
+        self.0.push(event.clone())
+    }
+}
+
+/// Pass events to each of several observers, in order.
+#[derive(Default)]
+pub struct Tee<'a>(pub Vec<&'a mut dyn StepObserver>);
+
+impl<'a> StepObserver for Tee<'a> {
+    fn observe(&mut self, event: &StepEvent) {
+        for obs in self.0.iter_mut() {
+            obs.observe(event)
+        }
+    }
+}
+
+/// Stream events as JSON objects, one per line.
+/// Stops writing after the first write error, which it keeps.
+pub struct JsonLines<W: Write> {
+    pub out: W,
+    pub error: Option<std::io::Error>,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(out: W) -> JsonLines<W> {
+        JsonLines { out, error: None }
+    }
+}
+
+impl<W: Write> StepObserver for JsonLines<W> {
+    fn observe(&mut self, event: &StepEvent) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.out, "{}", json(event)) {
+                self.error = Some(e)
+            }
+        }
+    }
+}
+
+/// An event as a JSON object.  Symbols, values and expressions are
+/// given in their concrete syntax.
+pub fn json(event: &StepEvent) -> serde_json::Value {
+    use serde_json::json;
+    let (name, mut fields) = match &event.kind {
+        Kind::Stepped { at, depth, env } => (
+            "stepped",
+            json!({ "at": at.to_string(), "depth": depth, "env": env }),
+        ),
+        Kind::Event(Event::NestBegin(s)) => ("nestBegin", json!({ "sym": s.to_string() })),
+        Kind::Event(Event::NestEnd(s)) => ("nestEnd", json!({ "sym": s.to_string() })),
+        Kind::Event(Event::Put(s, v)) => {
+            ("put", json!({ "sym": s.to_string(), "val": v.to_string() }))
+        }
+        Kind::Event(Event::Get(s, v)) => {
+            ("get", json!({ "sym": s.to_string(), "val": v.to_string() }))
+        }
+        Kind::Event(Event::GetWait(s, v)) => (
+            "getWait",
+            json!({ "sym": s.to_string(), "val": v.to_string() }),
+        ),
+        Kind::Event(Event::Link(v1, v2)) => (
+            "link",
+            json!({ "target": v1.to_string(), "val": v2.to_string() }),
+        ),
+        Kind::Event(Event::Spawn(s)) => ("spawn", json!({ "child": s.to_string() })),
+        Kind::Event(Event::Halt(v)) => ("halt", json!({ "val": v.to_string() })),
+        Kind::Error(e) => ("error", json!({ "error": e.to_string() })),
+        Kind::WaitPtr(s) => ("waitPtr", json!({ "sym": s.to_string() })),
+        Kind::WaitHalt(s) => ("waitHalt", json!({ "proc": s.to_string() })),
+    };
+    fields["step"] = json!(event.step);
+    fields["proc"] = json!(event.proc.to_string());
+    fields["event"] = json!(name);
+    fields
+}
+
+impl fmt::Display for StepEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}: ", self.step, self.proc)?;
+        match &self.kind {
+            Kind::Stepped { at, .. } => write!(f, "step {}", at),
+            Kind::Event(e) => write!(f, "{}", e),
+            Kind::Error(e) => write!(f, "error {}", e),
+            Kind::WaitPtr(s) => write!(f, "wait for pointer {}", s),
+            Kind::WaitHalt(s) => write!(f, "wait for process {}", s),
+        }
+    }
+}
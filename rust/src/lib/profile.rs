@@ -0,0 +1,219 @@
+//! Execution profiler.
+//!
+//! A `StepObserver` that counts steps per process, per expression form
+//! and per box, the highest stack depth and largest env, and store reads
+//! and writes per symbol namespace.  It reports as a table, or as folded
+//! stacks (`proc;#nest;box f;form count`) for flamegraph tools.
+//!
+//! Box steps are counted from the `Extract` step that enters the box until
+//! the stack drops below the depth of its caller (below any application
+//! frames that the box consumes); steps of boxes called from a box count
+//! for the callee only.
+
+use crate::ast::{step::Event, Exp, Id, Sym, Val};
+use crate::observe::{Kind, StepEvent, StepObserver};
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// The name of an expression form, as counted by the profiler.
+pub fn form(e: &Exp) -> &'static str {
+    use Exp::*;
+    match e {
+        Nest(_, _) => "nest",
+        Spawn(_, _) => "spawn",
+        Put(_, _) => "put",
+        Get(_) => "get",
+        GetWait(_) => "get-wait",
+        Link(_) => "link",
+        AssertEq(_, _, _) => "assert",
+        Lambda(_, _) => "lambda",
+        App(_, _) => "app",
+        Let(_, _, _) => "let",
+        Ret(_) => "ret",
+        Ret_(_) => "ret_",
+        Switch(_, _) => "switch",
+        Branches(_) => "branches",
+        Project(_, _) => "project",
+        LetBx(_, _, _) => "letBox",
+        Extract(_) => "extract",
+        Hole => "hole",
+        Loc(_, e) => form(e),
+    }
+}
+
+/// The namespace of a (put) symbol: the nests it was put within,
+/// or `None` for symbols put outside of any nest.
+pub fn namespace(s: &Sym) -> Option<Sym> {
+    match s {
+        Sym::Nest(n, s) => match namespace(s) {
+            None => Some((**n).clone()),
+            Some(ns) => Some(Sym::Nest(n.clone(), Box::new(ns))),
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Frame {
+    Nest(Sym),
+    /// A box, running at or above this stack depth.
+    Box(Id, usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub steps: usize,
+    pub proc_steps: HashMap<Sym, usize>,
+    pub form_steps: HashMap<&'static str, usize>,
+    pub box_calls: HashMap<Id, usize>,
+    pub box_steps: HashMap<Id, usize>,
+    pub max_depth: usize,
+    pub max_env: usize,
+    pub reads: HashMap<Option<Sym>, usize>,
+    pub writes: HashMap<Option<Sym>, usize>,
+    /// Steps per folded stack.
+    pub stacks: HashMap<String, usize>,
+    frames: HashMap<Sym, Vec<Frame>>,
+    /// Applications stepped just before the next step, per process.
+    apps: HashMap<Sym, usize>,
+}
+
+impl Profile {
+    fn stepped(&mut self, proc: &Sym, at: &Exp, depth: usize, env: usize) {
+        self.steps += 1;
+        *self.proc_steps.entry(proc.clone()).or_insert(0) += 1;
+        *self.form_steps.entry(form(at)).or_insert(0) += 1;
+        self.max_depth = self.max_depth.max(depth);
+        self.max_env = self.max_env.max(env);
+        let frames = self.frames.entry(proc.clone()).or_default();
+        while let Some(Frame::Box(_, d)) = frames.last() {
+            if depth < *d {
+                frames.pop();
+            } else {
+                break;
+            }
+        }
+        let mut stack = proc.to_string();
+        for fr in frames.iter() {
+            match fr {
+                Frame::Nest(s) => stack.push_str(&format!(";#{}", s)),
+                Frame::Box(f, _) => stack.push_str(&format!(";box {}", f)),
+            }
+        }
+        stack.push_str(&format!(";{}", form(at)));
+        *self.stacks.entry(stack).or_insert(0) += 1;
+        if let Some(Frame::Box(f, _)) = frames
+            .iter()
+            .rev()
+            .find(|fr| matches!(fr, Frame::Box(_, _)))
+        {
+            *self.box_steps.entry(f.clone()).or_insert(0) += 1;
+        }
+        let apps = self.apps.entry(proc.clone()).or_insert(0);
+        match crate::step::unloc(at) {
+            Exp::App(_, _) => *apps += 1,
+            Exp::Extract(Val::Var(f)) => {
+                *self.box_calls.entry(f.clone()).or_insert(0) += 1;
+                frames.push(Frame::Box(f.clone(), depth.saturating_sub(*apps)));
+                *apps = 0
+            }
+            _ => *apps = 0,
+        }
+    }
+
+    fn event(&mut self, proc: &Sym, e: &Event) {
+        match e {
+            Event::NestBegin(s) => self
+                .frames
+                .entry(proc.clone())
+                .or_default()
+                .push(Frame::Nest(s.clone())),
+            Event::NestEnd(_) => {
+                let frames = self.frames.entry(proc.clone()).or_default();
+                if let Some(i) = frames.iter().rposition(|fr| matches!(fr, Frame::Nest(_))) {
+                    frames.truncate(i)
+                }
+            }
+            Event::Put(s, _) => *self.writes.entry(namespace(s)).or_insert(0) += 1,
+            Event::Get(s, _)
+            | Event::GetWait(s, _)
+            | Event::Link(Val::Ptr(s), _)
+            | Event::Link(Val::Sym(s), _) => *self.reads.entry(namespace(s)).or_insert(0) += 1,
+            Event::Link(_, _) | Event::Spawn(_) | Event::Halt(_) => (),
+        }
+    }
+
+    /// Folded stacks, one `frames count` line per distinct stack.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks
+            .into_iter()
+            .map(|(s, n)| format!("{} {}\n", s, n))
+            .collect()
+    }
+}
+
+impl StepObserver for Profile {
+    fn observe(&mut self, event: &StepEvent) {
+        match &event.kind {
+            Kind::Stepped { at, depth, env } => self.stepped(&event.proc, at, *depth, *env),
+            Kind::Event(e) => self.event(&event.proc, e),
+            Kind::Error(_) | Kind::WaitPtr(_) | Kind::WaitHalt(_) => (),
+        }
+    }
+}
+
+/// Rows sorted by count (most first), then by name.
+fn rows<K: fmt::Display>(counts: &HashMap<K, usize>) -> Vec<(String, usize)> {
+    let mut rows: Vec<_> = counts.iter().map(|(k, n)| (k.to_string(), *n)).collect();
+    rows.sort_by(|(k1, n1), (k2, n2)| n2.cmp(n1).then(k1.cmp(k2)));
+    rows
+}
+
+fn ns_name(ns: &Option<Sym>) -> String {
+    match ns {
+        None => "(top)".to_string(),
+        Some(s) => s.to_string(),
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "steps: {}", self.steps)?;
+        writeln!(f, "max stack depth: {}", self.max_depth)?;
+        writeln!(f, "max env size: {}", self.max_env)?;
+        writeln!(f, "\n{:<24} {:>8}", "process", "steps")?;
+        for (p, n) in rows(&self.proc_steps) {
+            writeln!(f, "{:<24} {:>8}", p, n)?;
+        }
+        writeln!(f, "\n{:<24} {:>8}", "form", "steps")?;
+        for (e, n) in rows(&self.form_steps) {
+            writeln!(f, "{:<24} {:>8}", e, n)?;
+        }
+        if !self.box_calls.is_empty() {
+            writeln!(f, "\n{:<24} {:>8} {:>8}", "box", "calls", "steps")?;
+            for (b, n) in rows(&self.box_calls) {
+                let steps = self.box_steps.get(&b).cloned().unwrap_or(0);
+                writeln!(f, "{:<24} {:>8} {:>8}", b, n, steps)?;
+            }
+        }
+        let mut nss: Vec<_> = self.reads.keys().chain(self.writes.keys()).collect();
+        nss.sort();
+        nss.dedup();
+        if !nss.is_empty() {
+            writeln!(f, "\n{:<24} {:>8} {:>8}", "namespace", "reads", "writes")?;
+            for ns in nss {
+                writeln!(
+                    f,
+                    "{:<24} {:>8} {:>8}",
+                    ns_name(ns),
+                    self.reads.get(ns).cloned().unwrap_or(0),
+                    self.writes.get(ns).cloned().unwrap_or(0)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
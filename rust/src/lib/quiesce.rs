@@ -0,0 +1,241 @@
+//! Deadlock and starvation diagnosis at quiescence.
+//!
+//! When stepping stops, some processes may be waiting forever.  We build
+//! the wait-for graph (an edge from each process waiting for a halt to the
+//! process it waits for) and explain each waiting process by following its
+//! edges to a root cause: a cycle of waits, a symbol that nobody will ever
+//! put, or a process that ended in an error.
+
+use crate::ast::{
+    step::{Error, Proc, System},
+    Sym,
+};
+use crate::step::Stop;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why a chain of waits can never make progress.
+#[derive(Debug, Clone)]
+pub enum Cause {
+    /// The chain reaches a cycle of processes waiting for each other to halt.
+    Cycle(Vec<Sym>),
+    /// The chain ends in a process waiting for a pointer to this symbol,
+    /// which is not in the store (and nobody is left to put it).
+    NeverPut(Sym),
+    /// The chain ends in a process that failed with this error.
+    Errored(Sym, Box<Error>),
+    /// The chain ends in a process that can still step
+    /// (the system is not quiescent).
+    Live(Sym),
+}
+
+/// A waiting process, the processes it (transitively) waits for to halt,
+/// and why it cannot continue.
+#[derive(Debug, Clone)]
+pub struct Stuck {
+    pub proc: Sym,
+    pub waits: Vec<Sym>,
+    pub cause: Cause,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuiescenceReport {
+    /// Whether no process can step.
+    pub quiescent: bool,
+    pub halted: Vec<Sym>,
+    pub errors: Vec<(Sym, Error)>,
+    /// Cycles of the wait-for graph, each listed once.
+    pub cycles: Vec<Vec<Sym>>,
+    /// Every waiting process, with its explanation.
+    pub stuck: Vec<Stuck>,
+}
+
+impl QuiescenceReport {
+    /// Did every process halt?
+    pub fn all_halted(&self) -> bool {
+        self.errors.is_empty() && self.stuck.is_empty() && self.quiescent
+    }
+}
+
+/// How a run ended, overall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every process halted.
+    Halted,
+    /// Some process failed.
+    Errored,
+    /// Some process waits forever.
+    Waiting,
+    /// Stepping stopped early, for the whole system or for some process.
+    OutOfFuel,
+}
+
+impl Outcome {
+    pub fn of(stop: &Stop, report: &QuiescenceReport) -> Outcome {
+        let starved = report
+            .errors
+            .iter()
+            .any(|(_, e)| matches!(e, Error::OutOfFuel(_)));
+        if matches!(stop, Stop::OutOfFuel(_)) || starved {
+            Outcome::OutOfFuel
+        } else if !report.errors.is_empty() {
+            Outcome::Errored
+        } else if !report.all_halted() {
+            Outcome::Waiting
+        } else {
+            Outcome::Halted
+        }
+    }
+
+    /// Process exit status for the outcome.
+    /// (1 is left for errors reading and parsing the program.)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Halted => 0,
+            Outcome::Errored => 3,
+            Outcome::Waiting => 4,
+            Outcome::OutOfFuel => 5,
+        }
+    }
+}
+
+/// Wait-for graph: each process waiting for a halt, and the process it waits for.
+pub fn wait_for(sys: &System) -> HashMap<Sym, Sym> {
+    sys.procs
+        .0
+        .iter()
+        .filter_map(|(p, proc)| match proc {
+            Proc::WaitingForHalt(_, q) => Some((p.clone(), q.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Analyze a (stepped) system.
+pub fn report(sys: &System) -> QuiescenceReport {
+    let graph = wait_for(sys);
+    let mut names: Vec<&Sym> = sys.procs.0.keys().collect();
+    names.sort();
+    let mut r = QuiescenceReport {
+        quiescent: !sys.procs.0.values().any(|p| crate::step::can_step(sys, p)),
+        ..QuiescenceReport::default()
+    };
+    for p in names.into_iter() {
+        match &sys.procs.0[p] {
+            Proc::Halted(_) => r.halted.push(p.clone()),
+            Proc::Error(_, e) => r.errors.push((p.clone(), e.clone())),
+            Proc::WaitingForPtr(_, _) | Proc::WaitingForHalt(_, _) => {
+                let stuck = explain(sys, &graph, p);
+                if let Cause::Cycle(c) = &stuck.cause {
+                    // Each cycle is listed once, starting from its least name.
+                    if !r.cycles.contains(c) {
+                        r.cycles.push(c.clone())
+                    }
+                }
+                r.stuck.push(stuck)
+            }
+            Proc::Spawn(_) | Proc::Running(_) => (),
+        }
+    }
+    r
+}
+
+fn explain(sys: &System, graph: &HashMap<Sym, Sym>, p: &Sym) -> Stuck {
+    let mut waits: Vec<Sym> = vec![];
+    let mut cur = p.clone();
+    loop {
+        if let Some(i) = waits.iter().position(|q| q == &cur) {
+            let mut cycle: Vec<Sym> = waits[i..].to_vec();
+            let least = (0..cycle.len()).min_by_key(|j| &cycle[*j]).unwrap();
+            cycle.rotate_left(least);
+            // Keep the path into the cycle, up to its entry; a process on
+            // the cycle waits only for the cycle itself.
+            waits.truncate(if i == 0 { 1 } else { i + 1 });
+            return Stuck {
+                proc: p.clone(),
+                waits: waits[1..].to_vec(),
+                cause: Cause::Cycle(cycle),
+            };
+        }
+        waits.push(cur.clone());
+        let cause = match sys.procs.0.get(&cur) {
+            Some(Proc::WaitingForHalt(_, _)) => {
+                cur = graph[&cur].clone();
+                continue;
+            }
+            Some(Proc::WaitingForPtr(_, s)) if !sys.store.0.contains_key(s) => {
+                Cause::NeverPut(s.clone())
+            }
+            Some(Proc::Error(_, e)) => Cause::Errored(cur.clone(), Box::new(e.clone())),
+            // Halted targets (and missing ones) let the waiter step.
+            _ => Cause::Live(cur.clone()),
+        };
+        return Stuck {
+            proc: p.clone(),
+            waits: waits[1..].to_vec(),
+            cause,
+        };
+    }
+}
+
+fn fmt_syms(f: &mut fmt::Formatter, syms: &[Sym], sep: &str) -> fmt::Result {
+    let mut i = syms.iter().peekable();
+    while let Some(s) = i.next() {
+        write!(f, "{}", s)?;
+        if i.peek().is_some() {
+            write!(f, "{}", sep)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ", self.proc)?;
+        if !self.waits.is_empty() {
+            write!(f, "waits for ")?;
+            fmt_syms(f, &self.waits, " -> ")?;
+            write!(f, ", which ")?;
+        }
+        match &self.cause {
+            Cause::Cycle(c) => {
+                write!(f, "waits in cycle ")?;
+                fmt_syms(f, c, " -> ")?;
+                write!(f, " -> {}", c[0])
+            }
+            Cause::NeverPut(s) => write!(f, "waits for pointer {}, which is never put", s),
+            Cause::Errored(_, e) => write!(f, "failed: {}", e),
+            Cause::Live(_) => write!(f, "can still step"),
+        }
+    }
+}
+
+impl fmt::Display for QuiescenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} halted, {} failed, {} waiting",
+            if self.quiescent {
+                "quiescent"
+            } else {
+                "not quiescent"
+            },
+            self.halted.len(),
+            self.errors.len(),
+            self.stuck.len()
+        )?;
+        for (p, e) in self.errors.iter() {
+            writeln!(f, "  {} failed: {}", p, e)?;
+        }
+        for c in self.cycles.iter() {
+            write!(f, "  wait cycle: ")?;
+            fmt_syms(f, c, " -> ")?;
+            writeln!(f, " -> {}", c[0])?;
+        }
+        for s in self.stuck.iter() {
+            writeln!(f, "  {}", s)?;
+        }
+        Ok(())
+    }
+}
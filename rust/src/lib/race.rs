@@ -0,0 +1,205 @@
+//! Write-write and read-write race detection on the store.
+//!
+//! Replays the timeline of a system with vector clocks.  Each process is
+//! ordered by its own steps; spawning orders the spawner before the new
+//! process, and a resolved link orders the put (or spawn) of the linked
+//! symbol, or the halt of the linked process, before the linking process.
+//! A blocking get reads after the last put (or spawn) of its symbol.
+//! Two accesses to the same store symbol by different processes race when
+//! at least one is a write and neither happens before the other.
+
+use crate::ast::{
+    step::{Event, System},
+    Sym, Val,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Vector clock: steps of each process known to happen before.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clock(pub HashMap<Sym, usize>);
+
+impl Clock {
+    fn tick(&mut self, p: &Sym) {
+        *self.0.entry(p.clone()).or_insert(0) += 1;
+    }
+
+    fn join(&mut self, other: &Clock) {
+        for (p, n) in other.0.iter() {
+            let m = self.0.entry(p.clone()).or_insert(0);
+            *m = (*m).max(*n);
+        }
+    }
+
+    fn get(&self, p: &Sym) -> usize {
+        self.0.get(p).cloned().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Read,
+    Write,
+}
+
+/// Access of a store symbol by a process.
+#[derive(Debug, Clone)]
+pub struct Access {
+    pub proc: Sym,
+    pub step: usize,
+    pub kind: Kind,
+    pub value: Val,
+    pub clock: Clock,
+}
+
+impl Access {
+    /// Does this access happen before the other one?
+    pub fn before(&self, other: &Access) -> bool {
+        self.clock.get(&self.proc) <= other.clock.get(&self.proc)
+    }
+}
+
+/// Two unordered, conflicting accesses to the same symbol.
+#[derive(Debug, Clone)]
+pub struct Race {
+    pub sym: Sym,
+    pub first: Access,
+    pub second: Access,
+}
+
+/// All accesses to store symbols, per symbol, in timeline order.
+pub fn accesses(sys: &System) -> HashMap<Sym, Vec<Access>> {
+    let mut clocks: HashMap<Sym, Clock> = HashMap::new();
+    // Clock of the last write of each symbol, and of each halt.
+    let mut writes: HashMap<Sym, Clock> = HashMap::new();
+    let mut halts: HashMap<Sym, Clock> = HashMap::new();
+    let mut acc: HashMap<Sym, Vec<Access>> = HashMap::new();
+    for st in sys.timeline.0.iter() {
+        let p = &st.proc;
+        let mut clock = clocks.remove(p).unwrap_or_default();
+        clock.tick(p);
+        let mut access = |sym: &Sym, kind: Kind, value: &Val, clock: &Clock| {
+            acc.entry(sym.clone()).or_default().push(Access {
+                proc: p.clone(),
+                step: st.step,
+                kind,
+                value: value.clone(),
+                clock: clock.clone(),
+            })
+        };
+        match &st.event {
+            Event::Put(s, v) => {
+                access(s, Kind::Write, v, &clock);
+                writes.insert(s.clone(), clock.clone());
+            }
+            Event::Get(s, v) => access(s, Kind::Read, v, &clock),
+            Event::GetWait(s, v) => {
+                if let Some(c) = writes.get(s) {
+                    clock.join(c)
+                }
+                access(s, Kind::Read, v, &clock)
+            }
+            Event::Spawn(s) => {
+                access(s, Kind::Write, &Val::Proc(s.clone()), &clock);
+                writes.insert(s.clone(), clock.clone());
+                clocks.insert(s.clone(), clock.clone());
+            }
+            Event::Link(Val::Sym(s), _) => {
+                if let Some(c) = writes.get(s) {
+                    clock.join(c)
+                }
+            }
+            Event::Link(Val::Proc(q), _) => {
+                if let Some(c) = halts.get(q) {
+                    clock.join(c)
+                }
+            }
+            Event::Halt(_) => {
+                halts.insert(p.clone(), clock.clone());
+            }
+            Event::Link(_, _) | Event::NestBegin(_) | Event::NestEnd(_) => (),
+        }
+        clocks.insert(p.clone(), clock);
+    }
+    acc
+}
+
+/// Detect races in the timeline of a (stepped) system.
+/// Reports each conflicting (symbol, process, process) combination once.
+pub fn detect(sys: &System) -> Vec<Race> {
+    let acc = accesses(sys);
+    let mut syms: Vec<&Sym> = acc.keys().collect();
+    syms.sort();
+    let mut seen = HashSet::new();
+    let mut races = vec![];
+    for s in syms {
+        let xs = &acc[s];
+        for (i, a) in xs.iter().enumerate() {
+            for b in xs[i + 1..].iter() {
+                if a.proc == b.proc
+                    || (a.kind == Kind::Read && b.kind == Kind::Read)
+                    || a.before(b)
+                    || b.before(a)
+                {
+                    continue;
+                }
+                let key = (s.clone(), a.proc.clone(), b.proc.clone(), a.kind, b.kind);
+                if seen.insert(key) {
+                    races.push(Race {
+                        sym: s.clone(),
+                        first: a.clone(),
+                        second: b.clone(),
+                    })
+                }
+            }
+        }
+    }
+    races
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Read => write!(f, "read"),
+            Kind::Write => write!(f, "write"),
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} by {} at step {} ({})",
+            self.kind, self.proc, self.step, self.value
+        )
+    }
+}
+
+impl fmt::Display for Race {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "race on {}: {} and {}",
+            self.sym, self.first, self.second
+        )
+    }
+}
+
+/// Print a race, followed by the traces of both processes.
+pub fn report(sys: &System, race: &Race) -> String {
+    let mut out = format!("{}\n", race);
+    for p in [&race.first.proc, &race.second.proc].iter() {
+        let trace = match sys.procs.0.get(p) {
+            Some(proc) => crate::step::proc_traces(proc)
+                .iter()
+                .map(|t| format!("{}", t))
+                .collect::<Vec<_>>()
+                .join("; "),
+            None => String::new(),
+        };
+        out.push_str(&format!("  {}: [{}]\n", p, trace));
+    }
+    out
+}
@@ -0,0 +1,271 @@
+//! Read-eval-print loop over one persistent system.
+//!
+//! Each input line continues the root process (`%`) in the env left by
+//! the previous line, so `let` and `box` definitions persist, as do the
+//! store and any spawned processes.  A line ending in `;` is a
+//! definition: it continues with `ret []`, and its bindings become the
+//! env of later lines.  Other lines leave the env as it was.
+//!
+//! Meta-commands:
+//!
+//! - `:spawn <exp>`: run the expression as a new process, in the current env.
+//! - `:store`, `:procs`, `:env`: show the store, processes, or root env.
+//! - `:trace`: show the trace of the last line.
+//! - `:help`, `:quit`.
+
+use crate::ast::{
+    step::{
+        Env, Error, Proc, Procs, Running, Stack, Store, StorePolicy, System, Timeline, Traces,
+        ValsEnv,
+    },
+    BxesEnv, Exp, Sym, Val,
+};
+use crate::check::FreeVars;
+use crate::schedule::Scheduler;
+use crate::step::unloc;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "enter an expression, or a definition ending in `;`, or:
+  :spawn <exp>   run <exp> as a new process
+  :store         show the store
+  :procs         show the processes
+  :env           show the root env
+  :trace         show the trace of the last line
+  :quit
+";
+
+/// How a line ended.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// The root process reached `ret v`, with nothing left to do.
+    Value(Val),
+    /// The root process failed; its env is as before the line.
+    Error(Box<Error>),
+    /// The root process is blocked, and nothing else can step.
+    Stuck(Box<Proc>),
+    /// Stepping ran out of fuel.
+    OutOfFuel,
+}
+
+pub struct Repl {
+    pub sys: System,
+    /// The root env, as left by the last line that finished.
+    pub env: Env,
+    /// The trace of the last line.
+    pub trace: Traces,
+    /// Most (global) system steps per input line.
+    pub max_steps: usize,
+    free_vars: FreeVars,
+    next_proc: i32,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
+
+/// The root process has finished the line: no frames left, and returning.
+fn finished(p: &Proc) -> bool {
+    match p {
+        Proc::Running(r) => {
+            r.stack.0.is_empty() && matches!(unloc(&r.cont), Exp::Ret(_) | Exp::Ret_(_))
+        }
+        _ => false,
+    }
+}
+
+/// Processes in name order, but for a finished root, which would halt
+/// (and lose its env) if stepped.
+struct Settle;
+
+impl Scheduler for Settle {
+    fn order(&mut self, procs: &Procs) -> Vec<Sym> {
+        let mut names: Vec<Sym> = procs
+            .0
+            .iter()
+            .filter(|(s, p)| **s != Sym::None || !finished(p))
+            .map(|(s, _)| s.clone())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+fn is_definition(input: &str) -> bool {
+    input.trim().ends_with(';')
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            sys: System {
+                store: Store(HashMap::new()),
+                procs: Procs(HashMap::new()),
+                step: 0,
+                timeline: Timeline::default(),
+                proc_steps: HashMap::new(),
+                policy: StorePolicy::default(),
+                seed: Store(HashMap::new()),
+            },
+            env: Env {
+                vals: ValsEnv(HashMap::new()),
+                bxes: BxesEnv(HashMap::new()),
+            },
+            trace: Traces(vec![]),
+            max_steps: 100000,
+            free_vars: FreeVars {
+                base: "_r_".to_string(),
+                index: 0,
+            },
+            next_proc: 0,
+        }
+    }
+
+    fn parse(&mut self, input: &str) -> Result<Exp, String> {
+        let input = input.trim();
+        let input = if is_definition(input) {
+            format!("{} ret []", input)
+        } else {
+            input.to_string()
+        };
+        let e = crate::parser::ExpParser::new()
+            .parse(&input)
+            .map_err(|e| format!("{}", e))?;
+        crate::cbpv::convert(&mut self.free_vars, &e)
+            .map_err(|_| "out of temporary names".to_string())
+    }
+
+    fn running(&self, cont: Exp) -> Proc {
+        Proc::Running(Running {
+            env: self.env.clone(),
+            stack: Stack(vec![]),
+            cont,
+            trace: Traces(vec![]),
+        })
+    }
+
+    /// Step the system, as `step::system_with` does, until nothing (but a
+    /// finished root) can step.  Returns false if out of fuel.
+    fn settle(&mut self) -> bool {
+        let limit = self.sys.step + self.max_steps;
+        while self.sys.step < limit {
+            if crate::step::system_with(&mut self.sys, &mut Settle).is_err() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Evaluate one line in the root process.
+    pub fn eval(&mut self, input: &str) -> Result<Outcome, String> {
+        let e = self.parse(input)?;
+        let root = self.running(e);
+        self.sys.procs.0.insert(Sym::None, root);
+        let settled = self.settle();
+        let root = self.sys.procs.0[&Sym::None].clone();
+        let outcome = match root {
+            Proc::Running(r) if finished(&Proc::Running(r.clone())) => {
+                let v = match unloc(&r.cont) {
+                    Exp::Ret(v) => crate::step::value(&r.env, v),
+                    Exp::Ret_(v) => Ok(v.clone()),
+                    _ => unreachable!(),
+                };
+                self.trace = r.trace.clone();
+                match v {
+                    Ok(v) => {
+                        if is_definition(input) {
+                            self.env = r.env
+                        }
+                        Outcome::Value(v)
+                    }
+                    Err(e) => Outcome::Error(Box::new(Error::Value(e))),
+                }
+            }
+            _ if !settled => Outcome::OutOfFuel,
+            Proc::Error(r, e) => {
+                self.trace = r.trace;
+                Outcome::Error(Box::new(e))
+            }
+            p => Outcome::Stuck(Box::new(p)),
+        };
+        Ok(outcome)
+    }
+
+    /// Run an expression as a new process, in the root env.
+    pub fn spawn(&mut self, input: &str) -> Result<Sym, String> {
+        let e = self.parse(input)?;
+        let mut s = Sym::Num(self.next_proc);
+        while self.sys.procs.0.contains_key(&s) || self.sys.store.0.contains_key(&s) {
+            self.next_proc += 1;
+            s = Sym::Num(self.next_proc);
+        }
+        self.next_proc += 1;
+        let p = self.running(e);
+        self.sys.store.0.insert(s.clone(), Val::Proc(s.clone()));
+        self.sys.procs.0.insert(s.clone(), p);
+        self.settle();
+        Ok(s)
+    }
+
+    /// Handle one input line.  Returns false when the session should end.
+    pub fn line(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let line = line.trim();
+        match line.split_once(char::is_whitespace).unwrap_or((line, "")) {
+            ("", _) => (),
+            (":quit", _) | (":q", _) => return Ok(false),
+            (":help", _) | (":h", _) => write!(out, "{}", HELP)?,
+            (":store", _) => writeln!(out, "{}", self.sys.store)?,
+            (":procs", _) => {
+                let mut ps: Vec<_> = self.sys.procs.0.keys().collect();
+                ps.sort();
+                for p in ps {
+                    writeln!(
+                        out,
+                        "{} => {}",
+                        p,
+                        crate::debug::status(&self.sys.procs.0[p])
+                    )?;
+                }
+            }
+            (":env", _) => {
+                writeln!(out, "vals = {}", self.env.vals)?;
+                writeln!(out, "bxes = {}", self.env.bxes)?;
+            }
+            (":trace", _) => writeln!(out, "{}", self.trace)?,
+            (":spawn", e) => match self.spawn(e) {
+                Ok(s) => writeln!(out, "spawned ~{}", s)?,
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+            (cmd, _) if cmd.starts_with(':') => {
+                writeln!(out, "unknown command: {} (try :help)", cmd)?
+            }
+            _ => match self.eval(line) {
+                Ok(Outcome::Value(v)) => writeln!(out, "{}", v)?,
+                Ok(Outcome::Error(e)) => writeln!(out, "error: {}", e)?,
+                Ok(Outcome::Stuck(p)) => writeln!(out, "stuck: {}", crate::debug::status(&p))?,
+                Ok(Outcome::OutOfFuel) => {
+                    writeln!(out, "out of fuel after {} steps", self.max_steps)?
+                }
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+        }
+        Ok(true)
+    }
+
+    /// Read lines until `:quit` or end of input.
+    pub fn session<R: BufRead>(&mut self, input: R, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "fumola> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.line(&line?, out)? {
+                return Ok(());
+            }
+            write!(out, "fumola> ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+}
@@ -0,0 +1,150 @@
+//! Process schedulers.
+//!
+//! Each system step considers every process once; a scheduler decides the
+//! order.  Since processes share the store, the order determines the
+//! interleaving of their effects (e.g., which of two puts wins).  All of the
+//! built-in policies are deterministic, given the same (seeded) state.
+
+use crate::ast::{step::Procs, Sym};
+
+use std::collections::HashMap;
+
+/// Scheduler consulted by `step::system_with` once per system step.
+pub trait Scheduler {
+    /// Order in which to step the processes during the next system step.
+    fn order(&mut self, procs: &Procs) -> Vec<Sym>;
+}
+
+fn names(procs: &Procs) -> Vec<Sym> {
+    let mut ps: Vec<Sym> = procs.0.keys().cloned().collect();
+    ps.sort();
+    ps
+}
+
+/// Processes in name order, every step.
+#[derive(Debug, Clone, Default)]
+pub struct ByName;
+
+impl Scheduler for ByName {
+    fn order(&mut self, procs: &Procs) -> Vec<Sym> {
+        names(procs)
+    }
+}
+
+/// Processes in name order, starting one process later each step.
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin {
+    pub next: usize,
+}
+
+impl Scheduler for RoundRobin {
+    fn order(&mut self, procs: &Procs) -> Vec<Sym> {
+        let mut ps = names(procs);
+        if !ps.is_empty() {
+            let n = self.next % ps.len();
+            ps.rotate_left(n);
+        }
+        self.next += 1;
+        ps
+    }
+}
+
+/// Processes with higher priority first; equal priorities in name order.
+/// Processes without a given priority have priority zero.
+#[derive(Debug, Clone, Default)]
+pub struct Priority {
+    pub priorities: HashMap<Sym, i32>,
+}
+
+impl Scheduler for Priority {
+    fn order(&mut self, procs: &Procs) -> Vec<Sym> {
+        let mut ps = names(procs);
+        ps.sort_by_key(|p| -self.priorities.get(p).cloned().unwrap_or(0));
+        ps
+    }
+}
+
+/// Processes in a pseudo-random order, determined by the seed.
+#[derive(Debug, Clone)]
+pub struct Random {
+    pub rng: Rng,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { rng: Rng(seed) }
+    }
+}
+
+impl Scheduler for Random {
+    fn order(&mut self, procs: &Procs) -> Vec<Sym> {
+        let mut ps = names(procs);
+        self.rng.shuffle(&mut ps);
+        ps
+    }
+}
+
+/// Small, seedable pseudo-random number generator (SplitMix64).
+#[derive(Debug, Clone)]
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish number in `0..n`, for `n > 0`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % (n as u64)) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, xs: &mut [T]) {
+        for i in (1..xs.len()).rev() {
+            let j = self.below(i + 1);
+            xs.swap(i, j);
+        }
+    }
+}
+
+/// Scheduling policy, by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    ByName,
+    RoundRobin,
+    Priority,
+    Random,
+}
+
+impl std::str::FromStr for Policy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Policy, String> {
+        match s {
+            "name" => Ok(Policy::ByName),
+            "round-robin" => Ok(Policy::RoundRobin),
+            "priority" => Ok(Policy::Priority),
+            "random" => Ok(Policy::Random),
+            _ => Err(format!(
+                "unknown schedule {} (expected name, round-robin, priority or random)",
+                s
+            )),
+        }
+    }
+}
+
+impl Policy {
+    /// Scheduler for this policy.
+    /// The seed is used only by `Random`; the priorities only by `Priority`.
+    pub fn scheduler(self, seed: u64, priorities: HashMap<Sym, i32>) -> Box<dyn Scheduler> {
+        match self {
+            Policy::ByName => Box::new(ByName),
+            Policy::RoundRobin => Box::new(RoundRobin::default()),
+            Policy::Priority => Box::new(Priority { priorities }),
+            Policy::Random => Box::new(Random::new(seed)),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Static scope checking.
+//!
+//! Finds the uses of unbound variables before any process runs, rather
+//! than as `ValueError::Undefined` or `ExtractError::Undefined` at
+//! runtime.  Like `step::Env`, the scope keeps value variables and box
+//! variables apart, and follows the same rules as stepping:
+//!
+//! - `let` binds values, and `let box` binds boxes, in their bodies only;
+//! - box code starts from an empty scope (extraction resets `vals`, and
+//!   boxes capture no boxes), except for the value bound by `box rec`;
+//! - a call-by-value form `` `(e) `` is evaluated where `cbpv::convert`
+//!   hoists its temporary: outside of any enclosing lambda, switch case,
+//!   or other form, up to the nearest `let`, `let box`, box or program.
+
+use crate::ast::{Branches, Cases, Exp, Id, Loc, Pat, RecordVal, Val, ValField};
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+/// Kind of variable, and environment in which it is looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum VarKind {
+    Val,
+    Box,
+}
+
+/// Use of a variable that is not bound where it appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unbound {
+    pub kind: VarKind,
+    pub name: Id,
+    /// Innermost expression containing the use.
+    pub loc: Option<Loc>,
+}
+
+impl fmt::Display for VarKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarKind::Val => write!(f, "value"),
+            VarKind::Box => write!(f, "box"),
+        }
+    }
+}
+
+impl fmt::Display for Unbound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unbound {} variable {}", self.kind, self.name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    vals: HashSet<Id>,
+    bxes: HashSet<Id>,
+}
+
+impl Scope {
+    fn bind(&self, p: &Pat) -> Scope {
+        let mut s = self.clone();
+        pattern(p, &mut s.vals);
+        s
+    }
+}
+
+fn pattern(p: &Pat, vals: &mut HashSet<Id>) {
+    match p {
+        Pat::Ignore => (),
+        Pat::Var(x) => drop(vals.insert(x.clone())),
+        Pat::Fields(fps) => fps.0.iter().for_each(|fp| pattern(&fp.pattern, vals)),
+        Pat::Case(fp) => pattern(&fp.pattern, vals),
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    unbound: Vec<Unbound>,
+    loc: Option<Loc>,
+}
+
+impl Checker {
+    fn unbound(&mut self, kind: VarKind, name: &Id) {
+        self.unbound.push(Unbound {
+            kind,
+            name: name.clone(),
+            loc: self.loc,
+        })
+    }
+
+    /// Check `e` in scope `s`, hoisting call-by-value forms to scope `h`.
+    fn exp(&mut self, s: &Scope, h: &Scope, e: &Exp) {
+        use Exp::*;
+        match e {
+            Loc(l, e) => {
+                let outer = self.loc.replace(*l);
+                self.exp(s, h, e);
+                self.loc = outer
+            }
+            Hole => (),
+            Ret(v) | Ret_(v) | Get(v) | GetWait(v) | Link(v) => self.val(s, h, v),
+            Put(v1, v2) | AssertEq(v1, _, v2) => {
+                self.val(s, h, v1);
+                self.val(s, h, v2)
+            }
+            Nest(v, e) | Spawn(v, e) => {
+                self.val(s, h, v);
+                self.exp(s, h, e)
+            }
+            App(e, v) | Project(e, v) => {
+                self.exp(s, h, e);
+                self.val(s, h, v)
+            }
+            Lambda(p, e) => self.exp(&s.bind(p), h, e),
+            Let(p, e1, e2) => {
+                self.exp(s, s, e1);
+                let s2 = s.bind(p);
+                self.exp(&s2, &s2, e2)
+            }
+            LetBx(p, e1, e2) => {
+                self.exp(s, s, e1);
+                let mut s2 = s.clone();
+                if let Pat::Var(x) = p {
+                    s2.bxes.insert(x.clone());
+                }
+                self.exp(&s2, &s2, e2)
+            }
+            Extract(Val::Var(x)) => {
+                if !s.bxes.contains(x) {
+                    self.unbound(VarKind::Box, x)
+                }
+            }
+            Extract(v) => self.val(s, h, v),
+            Switch(v, cs) => {
+                self.val(s, h, v);
+                self.cases(s, h, cs)
+            }
+            Branches(bs) => self.branches(s, h, bs),
+        }
+    }
+
+    fn cases(&mut self, s: &Scope, h: &Scope, cs: &Cases) {
+        match cs {
+            Cases::Empty => (),
+            Cases::Gather(cs1, cs2) => {
+                self.cases(s, h, cs1);
+                self.cases(s, h, cs2)
+            }
+            Cases::Case(c) => {
+                self.val(s, h, &c.label);
+                self.exp(&s.bind(&c.pattern), h, &c.body)
+            }
+        }
+    }
+
+    fn branches(&mut self, s: &Scope, h: &Scope, bs: &Branches) {
+        match bs {
+            Branches::Empty => (),
+            Branches::Gather(bs1, bs2) => {
+                self.branches(s, h, bs1);
+                self.branches(s, h, bs2)
+            }
+            Branches::Branch(b) => {
+                self.val(s, h, &b.label);
+                self.exp(s, h, &b.body)
+            }
+        }
+    }
+
+    fn val(&mut self, s: &Scope, h: &Scope, v: &Val) {
+        use Val::*;
+        match v {
+            Var(x) => {
+                if !s.vals.contains(x) {
+                    self.unbound(VarKind::Val, x)
+                }
+            }
+            CallByValue(e) => self.exp(h, h, e),
+            Bx(bx) => {
+                let mut code = Scope::default();
+                if let Some(name) = &bx.name {
+                    code.vals.insert(name.clone());
+                }
+                self.exp(&code, &code, &bx.code)
+            }
+            Record(RecordVal(fs)) => fs.iter().for_each(|f| self.field(s, h, f)),
+            RecordExt(v, f) => {
+                self.val(s, h, v);
+                self.field(s, h, f)
+            }
+            Variant(v1, v2) => {
+                self.val(s, h, v1);
+                self.val(s, h, v2)
+            }
+            Sym(_) | Ptr(_) | Proc(_) | Num(_) => (),
+        }
+    }
+
+    fn field(&mut self, s: &Scope, h: &Scope, f: &ValField) {
+        self.val(s, h, &f.label);
+        self.val(s, h, &f.value)
+    }
+}
+
+/// All uses of unbound variables in a (closed) program, in source order.
+pub fn check(e: &Exp) -> Vec<Unbound> {
+    let mut c = Checker::default();
+    let top = Scope::default();
+    c.exp(&top, &top, e);
+    c.unbound
+}
+
+/// The variables that `e` uses without binding them, such as those
+/// of a nest body that it takes from its environment.
+pub fn free(e: &Exp) -> BTreeSet<(VarKind, Id)> {
+    check(e).into_iter().map(|u| (u.kind, u.name)).collect()
+}
+
+/// One `line:col: unbound ...` line per unbound use in `src`.
+pub fn report(src: &str, unbound: &[Unbound]) -> String {
+    unbound
+        .iter()
+        .map(|u| match u.loc {
+            Some(l) => {
+                let (line, col) = crate::cover::line_col(src, l.start);
+                format!("{}:{}: {}\n", line, col, u)
+            }
+            None => format!("{}\n", u),
+        })
+        .collect()
+}
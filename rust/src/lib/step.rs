@@ -1,23 +1,30 @@
 use crate::ast::{
     step::{
-        Env, Error, ExtractError, Frame, FrameCont, Halted, InternalError, PatternError, Proc,
-        Procs, ProjectError, Running, Signal, Stack, Store, SwitchError, System, Trace, Traces,
-        ValsEnv, ValueError,
+        Env, Error, Event, ExtractError, Frame, FrameCont, Halted, InternalError, Limit,
+        PatternError, Proc, Procs, ProjectError, PutPolicy, Running, Signal, Stack, Stamp, Store,
+        StorePolicy, SwitchError, System, Trace, Traces, ValsEnv, ValueError,
     },
     Branch, Branches, BxesEnv, Case, Cases, Exp, FieldPat, Pat, RecordVal, Sym, Val, ValField,
 };
 
+use crate::observe::{Ignore, Kind, StepEvent, StepObserver};
+use crate::schedule::{ByName, Scheduler};
+
 use std::collections::HashMap;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProcNoStep;
 
 /// step a process.
 /// returns None for processes that are blocked, Error, or Halted.
+/// Events of the step are appended to `events`.
 pub fn proc(
     procs: &Procs,
     store: &mut Store,
+    policy: &StorePolicy,
     proc: &mut Proc,
     spawn: &mut Vec<(Sym, Proc)>,
+    events: &mut Vec<Event>,
 ) -> Result<(), ProcNoStep> {
     let pr = std::mem::replace(proc, Proc::Spawn(Exp::Hole));
     match pr {
@@ -70,7 +77,10 @@ pub fn proc(
                     Proc::WaitingForHalt(mut r, sym) => {
                         let v = halted.retval.clone();
                         r.cont = Exp::Ret_(v.clone());
-                        r.trace.0.push(Trace::Link(Val::Proc(sym), v));
+                        r.trace
+                            .0
+                            .push(Trace::Link(Val::Proc(sym.clone()), v.clone()));
+                        events.push(Event::Link(Val::Proc(sym), v));
                         Proc::Running(r)
                     }
                     _ => unreachable!(),
@@ -82,12 +92,13 @@ pub fn proc(
                 Err(ProcNoStep)
             }
         },
-        Proc::Running(mut r) => match running(store, &mut r) {
+        Proc::Running(mut r) => match running(store, policy, &mut r, events) {
             Ok(()) => {
                 *proc = Proc::Running(r);
                 Ok(())
             }
             Err(Error::Signal(Signal::Halt(v))) => {
+                events.push(Event::Halt(v.clone()));
                 *proc = Proc::Halted(Halted {
                     retval: v,
                     trace: r.trace,
@@ -103,13 +114,14 @@ pub fn proc(
                 Ok(())
             }
             Err(Error::Signal(Signal::Spawn(s, env, cont))) => {
+                events.push(Event::Spawn(s.clone()));
                 spawn.push((
                     s,
                     Proc::Running(Running {
-                        env,
+                        env: *env,
                         trace: Traces(vec![]),
                         stack: Stack(vec![]),
-                        cont,
+                        cont: *cont,
                     }),
                 ));
                 *proc = Proc::Running(r.clone());
@@ -123,6 +135,27 @@ pub fn proc(
     }
 }
 
+/// The trace of a process, as a sequence of events.
+/// For an unfinished process, this includes the traces saved in its stack.
+pub fn proc_traces(p: &Proc) -> Vec<&Trace> {
+    fn running(r: &Running) -> Vec<&Trace> {
+        let mut ts: Vec<&Trace> = vec![];
+        for fr in r.stack.0.iter() {
+            ts.extend(fr.trace.0.iter());
+        }
+        ts.extend(r.trace.0.iter());
+        ts
+    }
+    match p {
+        Proc::Spawn(_) => vec![],
+        Proc::Running(r)
+        | Proc::WaitingForPtr(r, _)
+        | Proc::WaitingForHalt(r, _)
+        | Proc::Error(r, _) => running(r),
+        Proc::Halted(h) => h.trace.0.iter().collect(),
+    }
+}
+
 pub fn value_field(env: &Env, value_field: &ValField) -> Result<ValField, ValueError> {
     Ok(ValField {
         label: value(env, &value_field.label)?,
@@ -215,6 +248,7 @@ pub fn head(e: &Exp) -> Exp {
         Lambda(pat, _) => Lambda(pat.clone(), hole()),
         Put(v1, v2) => Put(v1.clone(), v2.clone()),
         Get(v) => Get(v.clone()),
+        GetWait(v) => GetWait(v.clone()),
         Link(v) => Link(v.clone()),
         Ret(v) => Ret(v.clone()),
         Ret_(v) => Ret_(v.clone()),
@@ -223,6 +257,7 @@ pub fn head(e: &Exp) -> Exp {
         LetBx(pat, _e1, _e2) => LetBx(pat.clone(), hole(), hole()),
         Extract(v) => Extract(v.clone()),
         Hole => Hole,
+        Loc(l, e) => Loc(*l, Box::new(head(e))),
         App(_e1, v) => App(hole(), v.clone()),
         Project(_e, v) => Project(hole(), v.clone()),
         Branches(b) => Branches(head_branches(b)),
@@ -230,14 +265,31 @@ pub fn head(e: &Exp) -> Exp {
     }
 }
 
-pub fn head_branches(_b: &Branches) -> Branches {
-    // to do -- for each branch case, keep pattern and hole the body.
-    Branches::Empty
+/// Shallow copy of branches: keep each label, and hole each body.
+pub fn head_branches(b: &Branches) -> Branches {
+    match b {
+        Branches::Empty => Branches::Empty,
+        Branches::Gather(b1, b2) => {
+            Branches::Gather(Box::new(head_branches(b1)), Box::new(head_branches(b2)))
+        }
+        Branches::Branch(br) => Branches::Branch(Branch {
+            label: br.label.clone(),
+            body: Box::new(Exp::Hole),
+        }),
+    }
 }
 
-pub fn head_cases(_c: &Cases) -> Cases {
-    // to do -- for each branch case, keep pattern and hole the body.
-    Cases::Empty
+/// Shallow copy of cases: keep each label and pattern, and hole each body.
+pub fn head_cases(c: &Cases) -> Cases {
+    match c {
+        Cases::Empty => Cases::Empty,
+        Cases::Gather(c1, c2) => Cases::Gather(Box::new(head_cases(c1)), Box::new(head_cases(c2))),
+        Cases::Case(case) => Cases::Case(Case {
+            label: case.label.clone(),
+            pattern: case.pattern.clone(),
+            body: Box::new(Exp::Hole),
+        }),
+    }
 }
 
 pub fn into_symbol(v: Val) -> Result<Sym, Error> {
@@ -264,6 +316,65 @@ pub fn put_symbol(stack: &Stack, s: Sym) -> Sym {
     r
 }
 
+/// The path of the innermost of the given nests (outermost first), as
+/// `put_symbol` prefixes the names put within them; `None` for no nests.
+pub fn nest_path<'a>(nests: impl DoubleEndedIterator<Item = &'a Sym>) -> Option<Sym> {
+    nests.rev().fold(None, |path, s| {
+        Some(match path {
+            None => s.clone(),
+            Some(p) => Sym::Nest(Box::new(s.clone()), Box::new(p)),
+        })
+    })
+}
+
+/// The policy of a put within the given nests (outermost first): that of
+/// the innermost nest with a policy of its own, or else the default.
+pub fn nest_policy<'a>(
+    policy: &StorePolicy,
+    nests: impl DoubleEndedIterator<Item = &'a Sym>,
+) -> PutPolicy {
+    let nests: Vec<&Sym> = nests.collect();
+    (1..=nests.len())
+        .rev()
+        .find_map(|n| {
+            let path = nest_path(nests[..n].iter().copied())?;
+            policy.nests.get(&path).cloned()
+        })
+        .unwrap_or(policy.default)
+}
+
+impl std::str::FromStr for PutPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PutPolicy, String> {
+        match s {
+            "overwrite" => Ok(PutPolicy::Overwrite),
+            "write-once" => Ok(PutPolicy::WriteOnce),
+            "overwrite-if-equal" => Ok(PutPolicy::OverwriteIfEqual),
+            _ => Err(format!(
+                "unknown store policy {} (expected overwrite, write-once or overwrite-if-equal)",
+                s
+            )),
+        }
+    }
+}
+
+/// May a put replace value `v0` with `v`, under the policy?
+pub fn overwrites(policy: PutPolicy, v0: &Val, v: &Val) -> bool {
+    match policy {
+        PutPolicy::Overwrite => true,
+        PutPolicy::WriteOnce => false,
+        PutPolicy::OverwriteIfEqual => v0 == v,
+    }
+}
+
+/// The expression under any source locations.
+pub fn unloc(e: &Exp) -> &Exp {
+    match e {
+        Exp::Loc(_, e) => unloc(e),
+        e => e,
+    }
+}
+
 /// Empty stack means halting program.  We trace the halting return.
 /// Stack with nest on top means we trace the return from the nest.
 pub fn stack_says_trace_ret(stack: &Stack) -> bool {
@@ -276,23 +387,31 @@ pub fn stack_says_trace_ret(stack: &Stack) -> bool {
 
 /// step a running process.
 /// returns None if already Blocked.
-pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
+pub fn running(
+    store: &mut Store,
+    policy: &StorePolicy,
+    r: &mut Running,
+    events: &mut Vec<Event>,
+) -> Result<(), Error> {
     // for each Exp form, step it, possibly to an Error.
     use std::mem::replace;
     use Exp::*;
     use Val::*;
     let h = head(&r.cont);
-    println!("running([cont = {}; ...])", h);
     let cont = replace(&mut r.cont, h);
     match cont {
         Hole => Err(Error::Internal(InternalError::Hole)),
+        Loc(_, e) => {
+            r.cont = *e;
+            running(store, policy, r, events)
+        }
         Ret(v) => {
             let v = value(&r.env, &v)?;
             if stack_says_trace_ret(&r.stack) {
                 r.trace.0.push(Trace::Ret(v.clone()));
             };
             r.cont = Ret_(v);
-            running(store, r)
+            running(store, policy, r, events)
         }
         Ret_(v) => {
             if r.stack.0.is_empty() {
@@ -307,6 +426,7 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
                     FrameCont::App(_) | FrameCont::Project(_) => Err(Error::NoStep),
                     FrameCont::Nest(s) => {
                         let tr = replace(&mut r.trace, fr.trace);
+                        events.push(Event::NestEnd(s.clone()));
                         r.trace.0.push(Trace::Nest(s, tr.0));
                         Ok(())
                     }
@@ -340,7 +460,7 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
                 match store.0.get(&s) {
                     None => {
                         r.cont = Ret_(Val::Proc(s.clone()));
-                        Err(Error::Signal(Signal::Spawn(s, r.env.clone(), *e)))
+                        Err(Error::Signal(Signal::Spawn(s, Box::new(r.env.clone()), e)))
                     }
                     Some(_) => Err(Error::Duplicate(s)),
                 }
@@ -350,6 +470,7 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
         Nest(v, e) => match value(&r.env, &v)? {
             Sym(s) => {
                 let trace = replace(&mut r.trace, Traces(vec![]));
+                events.push(Event::NestBegin(s.clone()));
                 r.stack.0.push(Frame {
                     cont: FrameCont::Nest(s),
                     trace,
@@ -460,7 +581,20 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
             let sym = into_symbol(v1)?;
             let v2 = value(&r.env, &v2)?;
             let sym = put_symbol(&r.stack, sym);
+            if let Some(v0) = store.0.get(&sym) {
+                let nests = r.stack.0.iter().filter_map(|fr| match &fr.cont {
+                    FrameCont::Nest(s) => Some(s),
+                    _ => None,
+                });
+                if !overwrites(nest_policy(policy, nests), v0, &v2) {
+                    return Err(Error::Overwrite(sym, v0.clone(), v2));
+                }
+                r.trace
+                    .0
+                    .push(Trace::Overwrite(sym.clone(), v0.clone(), v2.clone()));
+            }
             r.trace.0.push(Trace::Put(sym.clone(), v2.clone()));
+            events.push(Event::Put(sym.clone(), v2.clone()));
             store.0.insert(sym.clone(), v2);
             r.cont = Ret_(Ptr(sym));
             Ok(())
@@ -472,10 +606,30 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
                 None => return Err(Error::Undefined(sym)),
                 Some(v2) => v2.clone(),
             };
-            r.trace.0.push(Trace::Get(sym, v2.clone()));
+            r.trace.0.push(Trace::Get(sym.clone(), v2.clone()));
+            events.push(Event::Get(sym, v2.clone()));
             r.cont = Ret_(v2);
             Ok(())
         }
+        GetWait(v) => {
+            let v1 = value(&r.env, &v)?;
+            let sym = into_pointer(v1)?;
+            match store.0.get(&sym) {
+                None => {
+                    // Once the symbol is put, the process resumes here, and gets it.
+                    r.trace.0.push(Trace::Wait(sym.clone()));
+                    r.cont = GetWait(Ptr(sym.clone()));
+                    Err(Error::Signal(Signal::LinkWaitPtr(sym)))
+                }
+                Some(v2) => {
+                    let v2 = v2.clone();
+                    r.trace.0.push(Trace::Get(sym.clone(), v2.clone()));
+                    events.push(Event::GetWait(sym, v2.clone()));
+                    r.cont = Ret_(v2);
+                    Ok(())
+                }
+            }
+        }
         Switch(v, cases) => {
             let v = value(&r.env, &v)?;
             match v {
@@ -501,6 +655,7 @@ pub fn running(store: &mut Store, r: &mut Running) -> Result<(), Error> {
                         r.trace
                             .0
                             .push(Trace::Link(Val::Sym(sym.clone()), Val::Ptr(sym.clone())));
+                        events.push(Event::Link(Val::Sym(sym.clone()), Val::Ptr(sym.clone())));
                         r.cont = Ret_(Val::Ptr(sym));
                         Ok(())
                     }
@@ -568,20 +723,116 @@ pub fn switch_case(env: &Env, sym: &Sym, cases: Cases) -> Result<Case, Error> {
 }
 
 /// Step the system at most once, if possible.
+/// Processes are stepped in name order.
 pub fn system(sys: &mut System) -> Result<(), Error> {
+    system_with(sys, &mut ByName)
+}
+
+/// Step the system at most once, if possible,
+/// stepping processes in the order given by the scheduler.
+pub fn system_with(sys: &mut System, sched: &mut dyn Scheduler) -> Result<(), Error> {
+    system_observed(sys, sched, &mut Ignore)
+}
+
+/// Where a process would step from (shallow, see `head`), if it can.
+fn location(p: &Proc) -> Option<Kind> {
+    let stepped = |r: &Running| Kind::Stepped {
+        at: head(&r.cont),
+        depth: r.stack.0.len(),
+        env: r.env.vals.0.len() + r.env.bxes.0.len(),
+    };
+    match p {
+        Proc::Spawn(e) => Some(Kind::Stepped {
+            at: head(e),
+            depth: 0,
+            env: 0,
+        }),
+        Proc::Running(r) | Proc::WaitingForPtr(r, _) | Proc::WaitingForHalt(r, _) => {
+            Some(stepped(r))
+        }
+        Proc::Error(_, _) | Proc::Halted(_) => None,
+    }
+}
+
+/// Report a process step: where it stepped from, its timeline events,
+/// and whether it failed or blocked.
+fn observe(
+    obs: &mut dyn StepObserver,
+    step: usize,
+    proc: &Sym,
+    stepped: Kind,
+    events: &[Event],
+    after: &Proc,
+) {
+    let mut emit = |kind| {
+        obs.observe(&StepEvent {
+            step,
+            proc: proc.clone(),
+            kind,
+        })
+    };
+    emit(stepped);
+    for e in events.iter() {
+        emit(Kind::Event(e.clone()))
+    }
+    match after {
+        Proc::Error(_, e) => emit(Kind::Error(e.clone())),
+        Proc::WaitingForPtr(_, s) => emit(Kind::WaitPtr(s.clone())),
+        Proc::WaitingForHalt(_, s) => emit(Kind::WaitHalt(s.clone())),
+        _ => (),
+    }
+}
+
+/// Before the first step, keep the store that the host put in place.
+fn seed(sys: &mut System) {
+    if sys.step == 0 {
+        sys.seed = sys.store.clone()
+    }
+}
+
+/// Step the system at most once, like `system_with`,
+/// reporting each process step to the observer.
+pub fn system_observed(
+    sys: &mut System,
+    sched: &mut dyn Scheduler,
+    obs: &mut dyn StepObserver,
+) -> Result<(), Error> {
     if sys.procs.0.is_empty() {
         return Err(Error::NoProcs);
     }
+    seed(sys);
     let mut stepped = false;
     let mut spawned = vec![];
     let mut next_procs = HashMap::new();
-    for (s, p) in sys.procs.0.iter() {
+    for s in sched.order(&sys.procs).into_iter() {
         let mut spawn = vec![];
-        let mut p = p.clone(); // to do -- somehow avoid this clone.
-        match proc(&sys.procs, &mut sys.store, &mut p, &mut spawn) {
-            Ok(()) => stepped = true,
-            Err(ProcNoStep) => (),
+        let mut events = vec![];
+        let mut p = match sys.procs.0.get(&s) {
+            Some(p) => p.clone(), // to do -- somehow avoid this clone.
+            None => continue,
+        };
+        let at = location(&p);
+        if let Ok(()) = proc(
+            &sys.procs,
+            &mut sys.store,
+            &sys.policy,
+            &mut p,
+            &mut spawn,
+            &mut events,
+        ) {
+            stepped = true;
+            *sys.proc_steps.entry(s.clone()).or_insert(0) += 1;
+            if let Some(at) = at {
+                observe(obs, sys.step, &s, at, &events, &p)
+            }
         };
+        for event in events.into_iter() {
+            sys.timeline.0.push(Stamp {
+                step: sys.step,
+                proc: s.clone(),
+                event,
+            })
+        }
         next_procs.insert(s.clone(), p);
         for (s, p) in spawn.into_iter() {
             let prior = sys.store.0.insert(s.clone(), Val::Proc(s.clone()));
@@ -589,19 +840,202 @@ pub fn system(sys: &mut System) -> Result<(), Error> {
             assert!(prior.is_none());
         }
     }
-    sys.procs = Procs(next_procs);
+    // Processes that the scheduler did not consider are unchanged.
+    let prev = std::mem::replace(&mut sys.procs, Procs(next_procs));
+    for (s, p) in prev.0.into_iter() {
+        sys.procs.0.entry(s).or_insert(p);
+    }
     for (s, p) in spawned.into_iter() {
         let prior = sys.procs.0.insert(s, p);
         assert!(prior.is_none());
     }
     if stepped {
+        sys.step += 1;
         Ok(())
     } else {
         Err(Error::NoStep)
     }
 }
 
+/// Can the process step in the given system (without stepping it)?
+/// Mirrors `proc`: the process is neither finished nor blocked.
+pub fn can_step(sys: &System, p: &Proc) -> bool {
+    match p {
+        Proc::Spawn(_) | Proc::Running(_) => true,
+        Proc::Error(_, _) | Proc::Halted(_) => false,
+        Proc::WaitingForPtr(_, s) => sys.store.0.contains_key(s),
+        Proc::WaitingForHalt(_, s) => matches!(sys.procs.0.get(s), None | Some(Proc::Halted(_))),
+    }
+}
+
+/// Step only the named process, once, if possible.
+/// Its spawned processes are added to the system immediately.
+pub fn one(sys: &mut System, s: &Sym) -> Result<(), ProcNoStep> {
+    let mut p = sys.procs.0.get(s).ok_or(ProcNoStep)?.clone();
+    seed(sys);
+    let mut spawn = vec![];
+    let mut events = vec![];
+    let res = proc(
+        &sys.procs,
+        &mut sys.store,
+        &sys.policy,
+        &mut p,
+        &mut spawn,
+        &mut events,
+    );
+    for event in events.into_iter() {
+        sys.timeline.0.push(Stamp {
+            step: sys.step,
+            proc: s.clone(),
+            event,
+        })
+    }
+    sys.procs.0.insert(s.clone(), p);
+    for (s, p) in spawn.into_iter() {
+        let prior = sys.store.0.insert(s.clone(), Val::Proc(s.clone()));
+        assert!(prior.is_none());
+        let prior = sys.procs.0.insert(s, p);
+        assert!(prior.is_none());
+    }
+    if res.is_ok() {
+        sys.step += 1;
+        *sys.proc_steps.entry(s.clone()).or_insert(0) += 1;
+    }
+    res
+}
+
+/// Fuel limits for stepping.  `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct Fuel {
+    /// Most global system steps (counted by `System::step`).
+    pub steps: Option<usize>,
+    /// Most steps of any single process.
+    pub proc_steps: Option<usize>,
+    /// Most wall-clock time spent stepping.
+    pub time: Option<std::time::Duration>,
+}
+
+/// Why fully stepping a system stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// No process can step (each has halted, failed or is blocked).
+    Quiescent,
+    /// There are no processes.
+    NoProcs,
+    /// A global limit ran out while some processes could still step.
+    OutOfFuel(Limit),
+}
+
+/// Stop a process that could otherwise step, with an out-of-fuel error.
+fn out_of_fuel(p: Proc, limit: &Limit) -> Proc {
+    let err = Error::OutOfFuel(limit.clone());
+    match p {
+        Proc::Spawn(cont) => Proc::Error(
+            Running {
+                env: Env {
+                    vals: ValsEnv(HashMap::new()),
+                    bxes: BxesEnv(HashMap::new()),
+                },
+                stack: Stack(vec![]),
+                cont,
+                trace: Traces(vec![]),
+            },
+            err,
+        ),
+        Proc::Running(r) | Proc::WaitingForPtr(r, _) | Proc::WaitingForHalt(r, _) => {
+            Proc::Error(r, err)
+        }
+        p => p,
+    }
+}
+
+/// Stop each process that could step, and for which `stop` holds.
+fn stop_procs(
+    sys: &mut System,
+    limit: &Limit,
+    stop: impl Fn(&Sym) -> bool,
+    obs: &mut dyn StepObserver,
+) {
+    let stopping: Vec<Sym> = sys
+        .procs
+        .0
+        .iter()
+        .filter(|(s, p)| stop(s) && can_step(sys, p))
+        .map(|(s, _)| s.clone())
+        .collect();
+    for s in stopping.into_iter() {
+        let p = sys.procs.0.remove(&s).unwrap();
+        obs.observe(&StepEvent {
+            step: sys.step,
+            proc: s.clone(),
+            kind: Kind::Error(Error::OutOfFuel(limit.clone())),
+        });
+        sys.procs.0.insert(s, out_of_fuel(p, limit));
+    }
+}
+
 /// Fully step the system (to extent possible).
-pub fn fully(sys: &mut System) {
-    while let Ok(()) = system(sys) {}
+pub fn fully(sys: &mut System) -> Stop {
+    fully_with(sys, &mut ByName, &Fuel::default())
+}
+
+/// Fully step the system (to extent possible), using the given scheduler,
+/// until it stops or runs out of fuel.
+///
+/// Processes that exhaust their own step limit fail with
+/// `Error::OutOfFuel`; when a global limit runs out, so does every process
+/// that could still step.
+pub fn fully_with(sys: &mut System, sched: &mut dyn Scheduler, fuel: &Fuel) -> Stop {
+    fully_observed(sys, sched, fuel, &mut Ignore)
+}
+
+/// Fully step the system, like `fully_with`,
+/// reporting each process step to the observer.
+pub fn fully_observed(
+    sys: &mut System,
+    sched: &mut dyn Scheduler,
+    fuel: &Fuel,
+    obs: &mut dyn StepObserver,
+) -> Stop {
+    fully_between(sys, sched, fuel, obs, &mut |_| ())
+}
+
+/// Fully step the system, like `fully_observed`,
+/// calling `between` before each system step.
+pub fn fully_between(
+    sys: &mut System,
+    sched: &mut dyn Scheduler,
+    fuel: &Fuel,
+    obs: &mut dyn StepObserver,
+    between: &mut dyn FnMut(&mut System),
+) -> Stop {
+    let start = std::time::Instant::now();
+    loop {
+        between(sys);
+        if let Some(n) = fuel.proc_steps {
+            let steps = sys.proc_steps.clone();
+            stop_procs(
+                sys,
+                &Limit::ProcSteps(n),
+                |s| steps.get(s).cloned().unwrap_or(0) >= n,
+                obs,
+            );
+        }
+        let limit = match (fuel.steps, fuel.time) {
+            (Some(n), _) if sys.step >= n => Some(Limit::Steps(n)),
+            (_, Some(d)) if start.elapsed() >= d => Some(Limit::Time(d)),
+            _ => None,
+        };
+        if let Some(limit) = limit {
+            if sys.procs.0.values().any(|p| can_step(sys, p)) {
+                stop_procs(sys, &limit, |_| true, obs);
+                return Stop::OutOfFuel(limit);
+            }
+        }
+        match system_observed(sys, sched, obs) {
+            Ok(()) => (),
+            Err(Error::NoProcs) => return Stop::NoProcs,
+            Err(_) => return Stop::Quiescent,
+        }
+    }
 }
@@ -0,0 +1,1144 @@
+//! Static types.
+//!
+//! Following call-by-push-value (see `cbpv`), values and computations
+//! have separate types.  Value types are numbers, symbols, pointers `!A`,
+//! process handles `~A` (of processes that halt with an `A`), records,
+//! variants and boxes `{C}`.  Computation types are returners `ret A`,
+//! functions `A -> C` and branches `{$l => C; ...}`.  Records, variants
+//! and branches are typed by rows of labels, left open (`| 'r`) where
+//! more labels may follow.
+//!
+//! Types are inferred, without annotations.  Values bound by `let` and
+//! patterns are monomorphic; boxes bound by `let box` are generalized,
+//! so a box may be used at several types.  A well-typed program does not
+//! get stuck with `NoStep`, `NotASymbol`, `NotAPointer` or a
+//! `PatternError`, with two exceptions: labels computed at runtime (not
+//! written as literals) are not checked, and the store is untyped, so
+//! the contents of a literal pointer `!s`, or of a symbol linked with
+//! `&$s`, may have any type.
+//!
+//! Exhaustiveness is checked apart from types, by inferring the labels
+//! that flow into each `switch` and `<=` projection: a second inference
+//! treats boxes as monomorphic (so that labels flow from every call of a
+//! box), and does not let a switch add its case labels to the type of
+//! the value switched on (so that only labels of variants built by the
+//! program flow into it).  A case for a label that never flows in is
+//! redundant.  Switches on values whose labels are computed at runtime,
+//! or read from the store, are not judged.
+
+use crate::ast::{Branches, Cases, Exp, Id, Loc, Pat, Val};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+pub type TyVar = usize;
+
+/// Value types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValTy {
+    Var(TyVar),
+    Num,
+    Sym,
+    /// Pointer to a stored value.
+    Ptr(Box<ValTy>),
+    /// Process that halts with a value.
+    Proc(Box<ValTy>),
+    Record(Row<ValTy>),
+    Variant(Row<ValTy>),
+    /// Code box, extracting to a computation.
+    Box(Box<CompTy>),
+}
+
+/// Computation types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompTy {
+    Var(TyVar),
+    /// Returns a value (`F A` in CBPV).
+    Ret(Box<ValTy>),
+    /// Pops a value, then continues.
+    Fun(Box<ValTy>, Box<CompTy>),
+    /// Pops a symbol, then continues with the branch of that label.
+    Branches(Row<CompTy>),
+}
+
+/// Labelled types, and a row variable standing for other labels
+/// (`None` when there are no others).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row<T> {
+    pub fields: BTreeMap<String, T>,
+    pub rest: Option<TyVar>,
+}
+
+/// Type of a box bound by `let box`, general in `vars`.
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub vars: Vec<TyVar>,
+    pub ty: CompTy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    /// Innermost expression containing the error.
+    pub loc: Option<Loc>,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceKind {
+    Switch,
+    Project,
+}
+
+/// A switch or projection whose labels do not match the labels that
+/// flow into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exhaustiveness {
+    pub kind: ChoiceKind,
+    pub loc: Option<Loc>,
+    /// Labels that flow in without a case (or branch).
+    pub missing: Vec<String>,
+    /// Case labels that never flow in, or that an earlier case has.
+    pub redundant: Vec<String>,
+}
+
+/// Static label of a literal symbol or number.
+fn label(v: &Val) -> Option<String> {
+    match v {
+        Val::Sym(_) | Val::Num(_) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Env {
+    vals: HashMap<Id, ValTy>,
+    bxes: HashMap<Id, Scheme>,
+}
+
+/// A `&v` whose type depends on the type of `v`, which may not yet be
+/// known: linking a symbol gives a pointer, and linking a process gives
+/// its return value.
+#[derive(Debug, Clone)]
+struct PendingLink {
+    arg: ValTy,
+    res: ValTy,
+    loc: Option<Loc>,
+}
+
+struct Mismatch;
+
+enum ChoiceTy {
+    /// Type of the value switched on, and of the payload of each case.
+    Switch(ValTy, Vec<(Option<String>, ValTy)>),
+    /// Type of the computation projected from, and the label projected.
+    Project(CompTy, Option<String>),
+}
+
+/// Switch or projection met by a flow inference.
+struct Choice {
+    loc: Option<Loc>,
+    ty: ChoiceTy,
+}
+
+/// Types of rows: value types for records and variants, and computation
+/// types for branches.
+trait RowTy: Clone + fmt::Display + Sized {
+    fn rows(c: &Checker) -> &HashMap<TyVar, Row<Self>>;
+    fn rows_mut(c: &mut Checker) -> &mut HashMap<TyVar, Row<Self>>;
+    fn unify(c: &mut Checker, a: &Self, b: &Self) -> Result<(), Mismatch>;
+    fn resolve(c: &Checker, t: &Self) -> Self;
+    fn free(t: &Self, out: &mut HashSet<TyVar>);
+    fn rename(t: &Self, m: &HashMap<TyVar, TyVar>) -> Self;
+}
+
+impl RowTy for ValTy {
+    fn rows(c: &Checker) -> &HashMap<TyVar, Row<Self>> {
+        &c.val_rows
+    }
+    fn rows_mut(c: &mut Checker) -> &mut HashMap<TyVar, Row<Self>> {
+        &mut c.val_rows
+    }
+    fn unify(c: &mut Checker, a: &Self, b: &Self) -> Result<(), Mismatch> {
+        c.unify_val(a, b)
+    }
+    fn resolve(c: &Checker, t: &Self) -> Self {
+        c.val_ty(t)
+    }
+    fn free(t: &Self, out: &mut HashSet<TyVar>) {
+        free_val(t, out)
+    }
+    fn rename(t: &Self, m: &HashMap<TyVar, TyVar>) -> Self {
+        rename_val(t, m)
+    }
+}
+
+impl RowTy for CompTy {
+    fn rows(c: &Checker) -> &HashMap<TyVar, Row<Self>> {
+        &c.comp_rows
+    }
+    fn rows_mut(c: &mut Checker) -> &mut HashMap<TyVar, Row<Self>> {
+        &mut c.comp_rows
+    }
+    fn unify(c: &mut Checker, a: &Self, b: &Self) -> Result<(), Mismatch> {
+        c.unify_comp(a, b)
+    }
+    fn resolve(c: &Checker, t: &Self) -> Self {
+        c.comp_ty(t)
+    }
+    fn free(t: &Self, out: &mut HashSet<TyVar>) {
+        free_comp(t, out)
+    }
+    fn rename(t: &Self, m: &HashMap<TyVar, TyVar>) -> Self {
+        rename_comp(t, m)
+    }
+}
+
+fn free_val(t: &ValTy, out: &mut HashSet<TyVar>) {
+    use ValTy::*;
+    match t {
+        Var(v) => drop(out.insert(*v)),
+        Num | Sym => (),
+        Ptr(t) | Proc(t) => free_val(t, out),
+        Record(r) | Variant(r) => free_row(r, out),
+        Box(c) => free_comp(c, out),
+    }
+}
+
+fn free_comp(t: &CompTy, out: &mut HashSet<TyVar>) {
+    use CompTy::*;
+    match t {
+        Var(v) => drop(out.insert(*v)),
+        Ret(t) => free_val(t, out),
+        Fun(t, c) => {
+            free_val(t, out);
+            free_comp(c, out)
+        }
+        Branches(r) => free_row(r, out),
+    }
+}
+
+fn free_row<T: RowTy>(r: &Row<T>, out: &mut HashSet<TyVar>) {
+    r.fields.values().for_each(|t| T::free(t, out));
+    if let Some(v) = r.rest {
+        out.insert(v);
+    }
+}
+
+fn rename_var(v: TyVar, m: &HashMap<TyVar, TyVar>) -> TyVar {
+    m.get(&v).cloned().unwrap_or(v)
+}
+
+fn rename_val(t: &ValTy, m: &HashMap<TyVar, TyVar>) -> ValTy {
+    match t {
+        ValTy::Var(v) => ValTy::Var(rename_var(*v, m)),
+        ValTy::Num => ValTy::Num,
+        ValTy::Sym => ValTy::Sym,
+        ValTy::Ptr(t) => ValTy::Ptr(Box::new(rename_val(t, m))),
+        ValTy::Proc(t) => ValTy::Proc(Box::new(rename_val(t, m))),
+        ValTy::Record(r) => ValTy::Record(rename_row(r, m)),
+        ValTy::Variant(r) => ValTy::Variant(rename_row(r, m)),
+        ValTy::Box(c) => ValTy::Box(Box::new(rename_comp(c, m))),
+    }
+}
+
+fn rename_comp(t: &CompTy, m: &HashMap<TyVar, TyVar>) -> CompTy {
+    use CompTy::*;
+    match t {
+        Var(v) => Var(rename_var(*v, m)),
+        Ret(t) => Ret(Box::new(rename_val(t, m))),
+        Fun(t, c) => Fun(Box::new(rename_val(t, m)), Box::new(rename_comp(c, m))),
+        Branches(r) => Branches(rename_row(r, m)),
+    }
+}
+
+fn rename_row<T: RowTy>(r: &Row<T>, m: &HashMap<TyVar, TyVar>) -> Row<T> {
+    Row {
+        fields: r
+            .fields
+            .iter()
+            .map(|(l, t)| (l.clone(), T::rename(t, m)))
+            .collect(),
+        rest: r.rest.map(|v| rename_var(v, m)),
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    next: TyVar,
+    vals: HashMap<TyVar, ValTy>,
+    comps: HashMap<TyVar, CompTy>,
+    val_rows: HashMap<TyVar, Row<ValTy>>,
+    comp_rows: HashMap<TyVar, Row<CompTy>>,
+    links: Vec<PendingLink>,
+    errors: Vec<TypeError>,
+    loc: Option<Loc>,
+    /// Inferring label flow (see `exhaustiveness`) rather than types.
+    flow: bool,
+    choices: Vec<Choice>,
+    /// Types of variants with labels computed at runtime.
+    dynamic: Vec<ValTy>,
+}
+
+impl Checker {
+    fn fresh(&mut self) -> TyVar {
+        self.next += 1;
+        self.next
+    }
+
+    fn fresh_val(&mut self) -> ValTy {
+        ValTy::Var(self.fresh())
+    }
+
+    fn fresh_comp(&mut self) -> CompTy {
+        CompTy::Var(self.fresh())
+    }
+
+    fn open<T>(&mut self, fields: BTreeMap<String, T>) -> Row<T> {
+        Row {
+            fields,
+            rest: Some(self.fresh()),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(TypeError {
+            loc: self.loc,
+            message,
+        })
+    }
+
+    //
+    // # Substitution
+    //
+
+    /// Follow variable bindings at the top of a type.
+    fn walk_val(&self, t: &ValTy) -> ValTy {
+        match t {
+            ValTy::Var(v) => match self.vals.get(v) {
+                Some(t) => self.walk_val(t),
+                None => t.clone(),
+            },
+            _ => t.clone(),
+        }
+    }
+
+    fn walk_comp(&self, t: &CompTy) -> CompTy {
+        match t {
+            CompTy::Var(v) => match self.comps.get(v) {
+                Some(t) => self.walk_comp(t),
+                None => t.clone(),
+            },
+            _ => t.clone(),
+        }
+    }
+
+    /// All the fields of a row, following its row variables.
+    fn flatten<T: RowTy>(&self, r: &Row<T>) -> Row<T> {
+        let mut fields = r.fields.clone();
+        let mut rest = r.rest;
+        while let Some(r) = rest.and_then(|v| T::rows(self).get(&v)) {
+            for (l, t) in r.fields.iter() {
+                fields.entry(l.clone()).or_insert_with(|| t.clone());
+            }
+            rest = r.rest;
+        }
+        Row { fields, rest }
+    }
+
+    /// Value type, with all bound variables substituted.
+    fn val_ty(&self, t: &ValTy) -> ValTy {
+        match self.walk_val(t) {
+            ValTy::Ptr(t) => ValTy::Ptr(Box::new(self.val_ty(&t))),
+            ValTy::Proc(t) => ValTy::Proc(Box::new(self.val_ty(&t))),
+            ValTy::Record(r) => ValTy::Record(self.row(&r)),
+            ValTy::Variant(r) => ValTy::Variant(self.row(&r)),
+            ValTy::Box(c) => ValTy::Box(Box::new(self.comp_ty(&c))),
+            t => t,
+        }
+    }
+
+    /// Computation type, with all bound variables substituted.
+    fn comp_ty(&self, t: &CompTy) -> CompTy {
+        use CompTy::*;
+        match self.walk_comp(t) {
+            Ret(t) => Ret(Box::new(self.val_ty(&t))),
+            Fun(t, c) => Fun(Box::new(self.val_ty(&t)), Box::new(self.comp_ty(&c))),
+            Branches(r) => Branches(self.row(&r)),
+            t => t,
+        }
+    }
+
+    fn row<T: RowTy>(&self, r: &Row<T>) -> Row<T> {
+        let r = self.flatten(r);
+        Row {
+            fields: r
+                .fields
+                .iter()
+                .map(|(l, t)| (l.clone(), T::resolve(self, t)))
+                .collect(),
+            rest: r.rest,
+        }
+    }
+
+    //
+    // # Unification
+    //
+
+    fn unify_val(&mut self, a: &ValTy, b: &ValTy) -> Result<(), Mismatch> {
+        use ValTy::*;
+        match (self.walk_val(a), self.walk_val(b)) {
+            (Var(x), Var(y)) if x == y => Ok(()),
+            (Var(x), t) | (t, Var(x)) => {
+                let mut free = HashSet::new();
+                free_val(&self.val_ty(&t), &mut free);
+                if free.contains(&x) {
+                    return Err(Mismatch);
+                }
+                self.vals.insert(x, t);
+                Ok(())
+            }
+            (Num, Num) | (Sym, Sym) => Ok(()),
+            (Ptr(a), Ptr(b)) | (Proc(a), Proc(b)) => self.unify_val(&a, &b),
+            (Record(a), Record(b)) | (Variant(a), Variant(b)) => self.unify_row(&a, &b),
+            (Box(a), Box(b)) => self.unify_comp(&a, &b),
+            _ => Err(Mismatch),
+        }
+    }
+
+    fn unify_comp(&mut self, a: &CompTy, b: &CompTy) -> Result<(), Mismatch> {
+        use CompTy::*;
+        match (self.walk_comp(a), self.walk_comp(b)) {
+            (Var(x), Var(y)) if x == y => Ok(()),
+            (Var(x), t) | (t, Var(x)) => {
+                let mut free = HashSet::new();
+                free_comp(&self.comp_ty(&t), &mut free);
+                if free.contains(&x) {
+                    return Err(Mismatch);
+                }
+                self.comps.insert(x, t);
+                Ok(())
+            }
+            (Ret(a), Ret(b)) => self.unify_val(&a, &b),
+            (Fun(a1, c1), Fun(a2, c2)) => {
+                self.unify_val(&a1, &a2)?;
+                self.unify_comp(&c1, &c2)
+            }
+            (Branches(a), Branches(b)) => self.unify_row(&a, &b),
+            _ => Err(Mismatch),
+        }
+    }
+
+    fn bind_row<T: RowTy>(&mut self, v: TyVar, r: Row<T>) -> Result<(), Mismatch> {
+        let mut free = HashSet::new();
+        free_row(&self.row(&r), &mut free);
+        if free.contains(&v) {
+            return Err(Mismatch);
+        }
+        T::rows_mut(self).insert(v, r);
+        Ok(())
+    }
+
+    fn unify_row<T: RowTy>(&mut self, a: &Row<T>, b: &Row<T>) -> Result<(), Mismatch> {
+        let a = self.flatten(a);
+        let b = self.flatten(b);
+        for (l, ta) in a.fields.iter() {
+            if let Some(tb) = b.fields.get(l) {
+                T::unify(self, ta, tb)?
+            }
+        }
+        let only = |r: &Row<T>, other: &Row<T>| -> BTreeMap<String, T> {
+            r.fields
+                .iter()
+                .filter(|(l, _)| !other.fields.contains_key(*l))
+                .map(|(l, t)| (l.clone(), t.clone()))
+                .collect()
+        };
+        let (only_a, only_b) = (only(&a, &b), only(&b, &a));
+        match (a.rest, b.rest) {
+            (None, None) if only_a.is_empty() && only_b.is_empty() => Ok(()),
+            (Some(ra), None) if only_a.is_empty() => self.bind_row(
+                ra,
+                Row {
+                    fields: only_b,
+                    rest: None,
+                },
+            ),
+            (None, Some(rb)) if only_b.is_empty() => self.bind_row(
+                rb,
+                Row {
+                    fields: only_a,
+                    rest: None,
+                },
+            ),
+            (Some(ra), Some(rb)) if ra == rb && only_a.is_empty() && only_b.is_empty() => Ok(()),
+            (Some(ra), Some(rb)) if ra != rb => {
+                let rest = Some(self.fresh());
+                self.bind_row(
+                    ra,
+                    Row {
+                        fields: only_b,
+                        rest,
+                    },
+                )?;
+                self.bind_row(
+                    rb,
+                    Row {
+                        fields: only_a,
+                        rest,
+                    },
+                )
+            }
+            _ => Err(Mismatch),
+        }
+    }
+
+    /// Unify the type `found` with the type `expected`, or report both.
+    fn expect_val(&mut self, found: &ValTy, expected: &ValTy) {
+        if self.unify_val(found, expected).is_err() {
+            let msg = format!(
+                "expected {}, found {}",
+                self.val_ty(expected),
+                self.val_ty(found)
+            );
+            self.error(msg)
+        }
+    }
+
+    fn expect_comp(&mut self, found: &CompTy, expected: &CompTy) {
+        if self.unify_comp(found, expected).is_err() {
+            let msg = format!(
+                "expected {}, found {}",
+                self.comp_ty(expected),
+                self.comp_ty(found)
+            );
+            self.error(msg)
+        }
+    }
+
+    //
+    // # Links and box schemes
+    //
+
+    /// Resolve links whose argument type is known.
+    fn links(&mut self) {
+        let links = std::mem::take(&mut self.links);
+        let outer = self.loc;
+        for l in links {
+            self.loc = l.loc;
+            match self.walk_val(&l.arg) {
+                ValTy::Var(_) => self.links.push(l),
+                ValTy::Sym => {
+                    let ptr = ValTy::Ptr(Box::new(self.fresh_val()));
+                    self.expect_val(&l.res, &ptr)
+                }
+                ValTy::Proc(t) => self.expect_val(&l.res, &t),
+                t => {
+                    let msg = format!("expected sym or ~_, found {}", self.val_ty(&t));
+                    self.error(msg)
+                }
+            }
+        }
+        self.loc = outer
+    }
+
+    fn generalize(&mut self, env: &Env, ty: &CompTy) -> Scheme {
+        self.links();
+        let ty = self.comp_ty(ty);
+        let mut bound = HashSet::new();
+        for t in env.vals.values() {
+            free_val(&self.val_ty(t), &mut bound)
+        }
+        for s in env.bxes.values() {
+            let mut free = HashSet::new();
+            free_comp(&self.comp_ty(&s.ty), &mut free);
+            bound.extend(free.into_iter().filter(|v| !s.vars.contains(v)))
+        }
+        for l in self.links.iter() {
+            free_val(&self.val_ty(&l.arg), &mut bound);
+            free_val(&self.val_ty(&l.res), &mut bound);
+        }
+        let mut free = HashSet::new();
+        free_comp(&ty, &mut free);
+        let mut vars: Vec<_> = free.difference(&bound).cloned().collect();
+        vars.sort_unstable();
+        Scheme { vars, ty }
+    }
+
+    fn instantiate(&mut self, s: &Scheme) -> CompTy {
+        let m: HashMap<_, _> = s.vars.iter().map(|v| (*v, self.fresh())).collect();
+        rename_comp(&self.comp_ty(&s.ty), &m)
+    }
+
+    //
+    // # Checking
+    //
+
+    fn pattern(&mut self, p: &Pat, t: &ValTy, env: &mut Env) {
+        match p {
+            Pat::Ignore => (),
+            Pat::Var(x) => drop(env.vals.insert(x.clone(), t.clone())),
+            Pat::Fields(fps) => {
+                let mut fields = BTreeMap::new();
+                for fp in fps.0.iter() {
+                    let ft = self.fresh_val();
+                    self.pattern(&fp.pattern, &ft, env);
+                    match label(&fp.label) {
+                        Some(l) => drop(fields.entry(l).or_insert(ft)),
+                        None => self.error(format!("pattern label {} is not a literal", fp.label)),
+                    }
+                }
+                let r = ValTy::Record(self.open(fields));
+                self.expect_val(t, &r)
+            }
+            Pat::Case(_) => self.error("unsupported variant pattern".to_string()),
+        }
+    }
+
+    fn label_sym(&mut self, s: &Env, h: &Env, v: &Val) -> Option<String> {
+        let t = self.val(s, h, v);
+        self.expect_val(&t, &ValTy::Sym);
+        label(v)
+    }
+
+    /// Type of `e` in `s`, with call-by-value forms typed in `h` (see `scope`).
+    fn exp(&mut self, s: &Env, h: &Env, e: &Exp) -> CompTy {
+        use Exp::*;
+        match e {
+            Loc(l, e) => {
+                let outer = self.loc.replace(*l);
+                let t = self.exp(s, h, e);
+                self.loc = outer;
+                t
+            }
+            Hole => self.fresh_comp(),
+            Ret(v) | Ret_(v) => CompTy::Ret(Box::new(self.val(s, h, v))),
+            Put(v1, v2) => {
+                self.label_sym(s, h, v1);
+                let t = self.val(s, h, v2);
+                CompTy::Ret(Box::new(ValTy::Ptr(Box::new(t))))
+            }
+            Get(v) | GetWait(v) => {
+                let t = self.val(s, h, v);
+                let a = self.fresh_val();
+                self.expect_val(&t, &ValTy::Ptr(Box::new(a.clone())));
+                CompTy::Ret(Box::new(a))
+            }
+            Link(v) => {
+                let arg = self.val(s, h, v);
+                let res = self.fresh_val();
+                self.links.push(PendingLink {
+                    arg,
+                    res: res.clone(),
+                    loc: self.loc,
+                });
+                self.links();
+                CompTy::Ret(Box::new(res))
+            }
+            AssertEq(v1, b, v2) => {
+                let t1 = self.val(s, h, v1);
+                let t2 = self.val(s, h, v2);
+                if *b {
+                    self.expect_val(&t2, &t1)
+                }
+                CompTy::Ret(Box::new(ValTy::Record(Row {
+                    fields: BTreeMap::new(),
+                    rest: None,
+                })))
+            }
+            Nest(v, e) => {
+                self.label_sym(s, h, v);
+                let c = self.exp(s, h, e);
+                let r = CompTy::Ret(Box::new(self.fresh_val()));
+                self.expect_comp(&c, &r);
+                c
+            }
+            Spawn(v, e) => {
+                self.label_sym(s, h, v);
+                let c = self.exp(s, h, e);
+                let a = self.fresh_val();
+                self.expect_comp(&c, &CompTy::Ret(Box::new(a.clone())));
+                CompTy::Ret(Box::new(ValTy::Proc(Box::new(a))))
+            }
+            Lambda(p, e) => {
+                let a = self.fresh_val();
+                let mut s2 = s.clone();
+                self.pattern(p, &a, &mut s2);
+                let c = self.exp(&s2, h, e);
+                CompTy::Fun(Box::new(a), Box::new(c))
+            }
+            App(e, v) => {
+                let c = self.exp(s, h, e);
+                let a = self.val(s, h, v);
+                let r = self.fresh_comp();
+                self.expect_comp(&c, &CompTy::Fun(Box::new(a), Box::new(r.clone())));
+                r
+            }
+            Project(e, v) => {
+                let c = self.exp(s, h, e);
+                let r = self.fresh_comp();
+                let mut fields = BTreeMap::new();
+                if let Some(l) = self.label_sym(s, h, v) {
+                    fields.insert(l, r.clone());
+                }
+                let bs = CompTy::Branches(self.open(fields));
+                self.expect_comp(&c, &bs);
+                if self.flow {
+                    self.choices.push(Choice {
+                        loc: self.loc,
+                        ty: ChoiceTy::Project(c, label(v)),
+                    })
+                }
+                r
+            }
+            Let(p, e1, e2) => {
+                let c1 = self.exp(s, s, e1);
+                let a = self.fresh_val();
+                self.expect_comp(&c1, &CompTy::Ret(Box::new(a.clone())));
+                let mut s2 = s.clone();
+                self.pattern(p, &a, &mut s2);
+                self.exp(&s2, &s2, e2)
+            }
+            LetBx(p, e1, e2) => {
+                let c1 = self.exp(s, s, e1);
+                let c = self.fresh_comp();
+                let bx = ValTy::Box(Box::new(c.clone()));
+                self.expect_comp(&c1, &CompTy::Ret(Box::new(bx)));
+                let mut s2 = s.clone();
+                match p {
+                    Pat::Var(x) if self.flow => {
+                        let ty = c.clone();
+                        s2.bxes.insert(x.clone(), Scheme { vars: vec![], ty });
+                    }
+                    Pat::Var(x) => {
+                        let scheme = self.generalize(s, &c);
+                        s2.bxes.insert(x.clone(), scheme);
+                    }
+                    _ => self.error("unsupported box pattern".to_string()),
+                }
+                self.exp(&s2, &s2, e2)
+            }
+            Extract(Val::Var(x)) => match s.bxes.get(x) {
+                Some(scheme) => self.instantiate(scheme),
+                None => self.fresh_comp(),
+            },
+            Extract(v) => {
+                self.error(format!("cannot extract {}", v));
+                self.fresh_comp()
+            }
+            Switch(v, cs) => {
+                let t = self.val(s, h, v);
+                let r = self.fresh_comp();
+                let mut row = Row {
+                    fields: BTreeMap::new(),
+                    rest: None,
+                };
+                let mut payloads = vec![];
+                self.cases(s, h, cs, &r, &mut row, &mut payloads);
+                if self.flow {
+                    self.choices.push(Choice {
+                        loc: self.loc,
+                        ty: ChoiceTy::Switch(t, payloads),
+                    })
+                } else {
+                    self.expect_val(&t, &ValTy::Variant(row));
+                }
+                r
+            }
+            Branches(bs) => {
+                let mut row = Row {
+                    fields: BTreeMap::new(),
+                    rest: None,
+                };
+                self.branches(s, h, bs, &mut row);
+                CompTy::Branches(row)
+            }
+        }
+    }
+
+    /// Type the cases of a switch, adding their labels to `row`, and their
+    /// labels and payload types, in order, to `payloads`.
+    fn cases(
+        &mut self,
+        s: &Env,
+        h: &Env,
+        cs: &Cases,
+        r: &CompTy,
+        row: &mut Row<ValTy>,
+        payloads: &mut Vec<(Option<String>, ValTy)>,
+    ) {
+        match cs {
+            Cases::Empty => (),
+            Cases::Gather(cs1, cs2) => {
+                self.cases(s, h, cs1, r, row, payloads);
+                self.cases(s, h, cs2, r, row, payloads)
+            }
+            Cases::Case(c) => {
+                let a = self.fresh_val();
+                payloads.push((label(&c.label), a.clone()));
+                match self.label_sym(s, h, &c.label) {
+                    Some(l) if !row.fields.contains_key(&l) => {
+                        drop(row.fields.insert(l, a.clone()))
+                    }
+                    Some(_) => (),
+                    None => row.rest = row.rest.or_else(|| Some(self.fresh())),
+                }
+                let mut s2 = s.clone();
+                self.pattern(&c.pattern, &a, &mut s2);
+                let body = self.exp(&s2, h, &c.body);
+                self.expect_comp(&body, r)
+            }
+        }
+    }
+
+    fn branches(&mut self, s: &Env, h: &Env, bs: &Branches, row: &mut Row<CompTy>) {
+        match bs {
+            Branches::Empty => (),
+            Branches::Gather(bs1, bs2) => {
+                self.branches(s, h, bs1, row);
+                self.branches(s, h, bs2, row)
+            }
+            Branches::Branch(b) => {
+                let l = self.label_sym(s, h, &b.label);
+                let c = self.exp(s, h, &b.body);
+                match l {
+                    Some(l) if !row.fields.contains_key(&l) => drop(row.fields.insert(l, c)),
+                    Some(_) => (),
+                    None => row.rest = row.rest.or_else(|| Some(self.fresh())),
+                }
+            }
+        }
+    }
+
+    fn val(&mut self, s: &Env, h: &Env, v: &Val) -> ValTy {
+        use Val::*;
+        match v {
+            Num(_) => ValTy::Num,
+            Sym(_) => ValTy::Sym,
+            Ptr(_) => ValTy::Ptr(Box::new(self.fresh_val())),
+            Proc(_) => ValTy::Proc(Box::new(self.fresh_val())),
+            Var(x) => match s.vals.get(x) {
+                Some(t) => t.clone(),
+                None => self.fresh_val(),
+            },
+            CallByValue(e) => {
+                let c = self.exp(h, h, e);
+                let a = self.fresh_val();
+                self.expect_comp(&c, &CompTy::Ret(Box::new(a.clone())));
+                a
+            }
+            Bx(bx) => {
+                let c = self.fresh_comp();
+                let mut code = Env::default();
+                if let Some(name) = &bx.name {
+                    code.vals
+                        .insert(name.clone(), ValTy::Box(Box::new(c.clone())));
+                }
+                let t = self.exp(&code, &code, &bx.code);
+                self.expect_comp(&t, &c);
+                ValTy::Box(Box::new(c))
+            }
+            Variant(l, v) => {
+                let l = self.label_sym(s, h, l);
+                let t = self.val(s, h, v);
+                let mut fields = BTreeMap::new();
+                let dynamic = l.is_none();
+                if let Some(l) = l {
+                    fields.insert(l, t);
+                }
+                let t = ValTy::Variant(self.open(fields));
+                if dynamic && self.flow {
+                    self.dynamic.push(t.clone())
+                }
+                t
+            }
+            Record(r) => {
+                let mut row = Row {
+                    fields: BTreeMap::new(),
+                    rest: None,
+                };
+                for f in r.0.iter() {
+                    self.val(s, h, &f.label);
+                    let t = self.val(s, h, &f.value);
+                    match label(&f.label) {
+                        Some(l) => drop(row.fields.entry(l).or_insert(t)),
+                        None => row.rest = row.rest.or_else(|| Some(self.fresh())),
+                    }
+                }
+                ValTy::Record(row)
+            }
+            RecordExt(_, _) => {
+                self.error(format!("unsupported record extension {}", v));
+                self.fresh_val()
+            }
+        }
+    }
+}
+
+/// Type of a program, or all of its type errors, in source order.
+///
+/// Unbound variables are not errors here (see `scope`); they may have any
+/// type.
+pub fn check(e: &Exp) -> Result<CompTy, Vec<TypeError>> {
+    let mut c = Checker::default();
+    let top = Env::default();
+    let t = c.exp(&top, &top, e);
+    // A process halts by returning a value.
+    let r = CompTy::Ret(Box::new(c.fresh_val()));
+    c.expect_comp(&t, &r);
+    c.links();
+    if c.errors.is_empty() {
+        Ok(c.comp_ty(&t))
+    } else {
+        Err(c.errors)
+    }
+}
+
+impl Checker {
+    /// Unify the payload types of cases with those of the labels flowing
+    /// into their switches, until no more labels flow.
+    fn payloads(&mut self) {
+        let mut done = HashSet::new();
+        loop {
+            let mut more = false;
+            for i in 0..self.choices.len() {
+                if let ChoiceTy::Switch(t, payloads) = &self.choices[i].ty {
+                    let (t, payloads) = (t.clone(), payloads.clone());
+                    if let ValTy::Variant(row) = self.walk_val(&t) {
+                        let row = self.flatten(&row);
+                        for (j, (l, a)) in payloads.iter().enumerate() {
+                            let flows = l.as_ref().and_then(|l| row.fields.get(l));
+                            if let Some(b) = flows {
+                                if done.insert((i, j)) {
+                                    more = true;
+                                    let _ = self.unify_val(a, b);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            self.links();
+            if !more {
+                return;
+            }
+        }
+    }
+
+    /// Row variable of the labels of a variant type not (yet) known.
+    fn variant_rest(&self, t: &ValTy) -> Option<TyVar> {
+        match self.walk_val(t) {
+            ValTy::Variant(row) => self.flatten(&row).rest,
+            _ => None,
+        }
+    }
+
+    fn exhaustiveness(&self, choice: &Choice) -> Option<Exhaustiveness> {
+        let (kind, missing, redundant) = match &choice.ty {
+            ChoiceTy::Switch(t, payloads) => {
+                let row = match self.walk_val(t) {
+                    ValTy::Variant(row) => self.flatten(&row),
+                    // Nothing built by the program flows in.
+                    _ => return None,
+                };
+                let dynamic = self
+                    .dynamic
+                    .iter()
+                    .any(|d| row.rest.is_some() && self.variant_rest(d) == row.rest);
+                if dynamic {
+                    return None;
+                }
+                let labels: Vec<_> = payloads.iter().map(|(l, _)| l.clone()).collect();
+                // A case for a label computed at runtime may match any label.
+                let missing = if labels.iter().any(|l| l.is_none()) {
+                    vec![]
+                } else {
+                    row.fields
+                        .keys()
+                        .filter(|l| !labels.contains(&Some(l.to_string())))
+                        .cloned()
+                        .collect()
+                };
+                let mut redundant = vec![];
+                for (i, l) in labels.iter().enumerate() {
+                    if let Some(l) = l {
+                        if !row.fields.contains_key(l) || labels[..i].contains(&Some(l.clone())) {
+                            redundant.push(l.clone())
+                        }
+                    }
+                }
+                (ChoiceKind::Switch, missing, redundant)
+            }
+            ChoiceTy::Project(c, Some(l)) => match self.comp_ty(c) {
+                CompTy::Branches(row) if row.rest.is_none() && !row.fields.contains_key(l) => {
+                    (ChoiceKind::Project, vec![l.clone()], vec![])
+                }
+                _ => return None,
+            },
+            ChoiceTy::Project(_, None) => return None,
+        };
+        if missing.is_empty() && redundant.is_empty() {
+            None
+        } else {
+            Some(Exhaustiveness {
+                kind,
+                loc: choice.loc,
+                missing,
+                redundant,
+            })
+        }
+    }
+}
+
+/// Switches and projections of a program whose labels do not match the
+/// labels that flow into them, in source order.
+///
+/// Type errors are not reported here (see `check`); they may hide flows.
+pub fn exhaustiveness(e: &Exp) -> Vec<Exhaustiveness> {
+    let mut c = Checker {
+        flow: true,
+        ..Checker::default()
+    };
+    let top = Env::default();
+    c.exp(&top, &top, e);
+    c.links();
+    c.payloads();
+    let mut found: Vec<_> = c
+        .choices
+        .iter()
+        .filter_map(|ch| c.exhaustiveness(ch))
+        .collect();
+    found.sort_by_key(|x| x.loc.map(|l| l.start));
+    found
+}
+
+impl fmt::Display for Exhaustiveness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cases = |ls: &[String]| {
+            ls.iter()
+                .map(|l| format!("#{}", l))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self.kind {
+            ChoiceKind::Switch if !self.missing.is_empty() => {
+                write!(
+                    f,
+                    "switch is not exhaustive: no case for {}",
+                    cases(&self.missing)
+                )?;
+                if !self.redundant.is_empty() {
+                    write!(f, "; redundant cases {}", cases(&self.redundant))?;
+                }
+                Ok(())
+            }
+            ChoiceKind::Switch => {
+                write!(f, "switch has redundant cases {}", cases(&self.redundant))
+            }
+            ChoiceKind::Project => write!(
+                f,
+                "projection is not exhaustive: no branch for {}",
+                self.missing.join(", ")
+            ),
+        }
+    }
+}
+
+/// One `line:col: message` line per switch or projection in `src`.
+pub fn exhaustiveness_report(src: &str, found: &[Exhaustiveness]) -> String {
+    found
+        .iter()
+        .map(|x| match x.loc {
+            Some(l) => {
+                let (line, col) = crate::cover::line_col(src, l.start);
+                format!("{}:{}: {}\n", line, col, x)
+            }
+            None => format!("{}\n", x),
+        })
+        .collect()
+}
+
+/// One `line:col: message` line per type error in `src`.
+pub fn report(src: &str, errors: &[TypeError]) -> String {
+    errors
+        .iter()
+        .map(|e| match e.loc {
+            Some(l) => {
+                let (line, col) = crate::cover::line_col(src, l.start);
+                format!("{}:{}: {}\n", line, col, e)
+            }
+            None => format!("{}\n", e),
+        })
+        .collect()
+}
+
+//
+// # Printing
+//
+
+fn row_fmt<T: fmt::Display>(
+    f: &mut fmt::Formatter,
+    r: &Row<T>,
+    field: fn(&mut fmt::Formatter, &str, &T) -> fmt::Result,
+) -> fmt::Result {
+    let mut first = true;
+    for (l, t) in r.fields.iter() {
+        if !first {
+            write!(f, "; ")?;
+        }
+        first = false;
+        field(f, l, t)?;
+    }
+    if let Some(v) = r.rest {
+        if !first {
+            write!(f, " ")?;
+        }
+        write!(f, "| 'r{}", v)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ValTy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ValTy::*;
+        match self {
+            Var(v) => write!(f, "'a{}", v),
+            Num => write!(f, "num"),
+            Sym => write!(f, "sym"),
+            Ptr(t) => write!(f, "!{}", t),
+            Proc(t) => write!(f, "~{}", t),
+            Record(r) => {
+                write!(f, "[")?;
+                row_fmt(f, r, |f, l, t| write!(f, "{} => {}", l, t))?;
+                write!(f, "]")
+            }
+            Variant(r) => {
+                write!(f, "#[")?;
+                row_fmt(f, r, |f, l, t| write!(f, "{}({})", l, t))?;
+                write!(f, "]")
+            }
+            Box(c) => write!(f, "{{{}}}", c),
+        }
+    }
+}
+
+impl fmt::Display for CompTy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CompTy::*;
+        match self {
+            Var(v) => write!(f, "'c{}", v),
+            Ret(t) => write!(f, "ret {}", t),
+            Fun(t, c) => write!(f, "{} -> {}", t, c),
+            Branches(r) => {
+                write!(f, "{{")?;
+                row_fmt(f, r, |f, l, c| write!(f, "{} => {}", l, c))?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Name uniqueness of puts and spawns.
+//!
+//! Puts and spawns share one namespace, the store: spawning a process
+//! puts its name there, spawning a name already put fails with
+//! `Error::Duplicate`, and putting a name already put overwrites it (or,
+//! under a stricter store policy, fails with `Error::Overwrite`).
+//! In the spirit of Fungi's name types, we check statically that every
+//! put and spawn of a program uses a distinct name.
+//!
+//! Names are the symbol patterns of the effect analysis (see `effects`),
+//! with nests prefixing names as `step::put_symbol` does.  Distinct
+//! literal symbols, compound (`Bin`, `Tri`) or not, are distinct names,
+//! as are the same symbol put within different nests.  Two sites
+//! conflict when some name matches both, unless they are in different
+//! alternatives of a switch or projection; a site within a recursive
+//! call conflicts with itself, as does a box of unknown code (one read
+//! from the store, say), which may put any names.  No conflicts proves
+//! the names unique.
+
+use crate::ast::Exp;
+use crate::cbpv::FreeVarsNoNext;
+use crate::effects::{Site, SiteKind};
+
+/// Two sites that may use the same name; or one site (`second` is
+/// `None`) within a recursive call, which may run more than once, or in
+/// a box of unknown code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub first: Site,
+    pub second: Option<Site>,
+}
+
+impl Conflict {
+    /// Whether two sites use the same, exactly known name (rather than
+    /// names that might be the same).
+    pub fn definite(&self) -> bool {
+        match &self.second {
+            Some(s2) => self.first.sym.exact() && self.first.sym == s2.sym,
+            None => false,
+        }
+    }
+}
+
+/// Conflicting pairs of puts and spawns of a program, none when all of
+/// its names are distinct.
+pub fn check(e: &Exp) -> Result<Vec<Conflict>, FreeVarsNoNext> {
+    let sites: Vec<Site> = crate::effects::sites(e)?
+        .into_iter()
+        .filter(|s| s.kind != SiteKind::Get)
+        .collect();
+    let mut conflicts: Vec<Conflict> = vec![];
+    let key = |s: &Site| (s.kind, s.loc, s.sym.clone());
+    let mut add = |first: &Site, second: Option<&Site>| {
+        let c = Conflict {
+            first: first.clone(),
+            second: second.cloned(),
+        };
+        // The same pair of places, met again through another call.
+        if !conflicts.iter().any(|c2| {
+            key(&c2.first) == key(&c.first)
+                && c2.second.as_ref().map(key) == c.second.as_ref().map(key)
+        }) {
+            conflicts.push(c)
+        }
+    };
+    for (i, s1) in sites.iter().enumerate() {
+        if s1.many || s1.unknown {
+            add(s1, None)
+        }
+        for s2 in sites[i + 1..].iter() {
+            if s1.sym.overlaps(&s2.sym) && !s1.exclusive(s2) {
+                add(s1, Some(s2))
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+fn at(src: &str, s: &Site) -> String {
+    match s.loc {
+        Some(l) => {
+            let (line, col) = crate::cover::line_col(src, l.start);
+            format!("{}:{}", line, col)
+        }
+        None => "?".to_string(),
+    }
+}
+
+/// One line per conflict, placed at its second site.
+pub fn report(src: &str, conflicts: &[Conflict]) -> String {
+    conflicts
+        .iter()
+        .map(|c| {
+            let s1 = &c.first;
+            let reuse = if c.definite() { "reuses" } else { "may reuse" };
+            match &c.second {
+                None if s1.unknown => format!(
+                    "{}: box of unknown code may put any names, more than once\n",
+                    at(src, s1)
+                ),
+                None => format!(
+                    "{}: {} {} may run more than once, in a recursive call\n",
+                    at(src, s1),
+                    s1.kind,
+                    s1.sym
+                ),
+                // One site, reached through different calls.
+                Some(s2) if s2.loc == s1.loc => format!(
+                    "{}: {} {} runs again, and {} {}\n",
+                    at(src, s2),
+                    s2.kind,
+                    s2.sym,
+                    reuse,
+                    s1.sym
+                ),
+                Some(s2) => format!(
+                    "{}: {} {} {} {} {} at {}\n",
+                    at(src, s2),
+                    s2.kind,
+                    s2.sym,
+                    reuse,
+                    s1.kind,
+                    s1.sym,
+                    at(src, s1)
+                ),
+            }
+        })
+        .collect()
+}
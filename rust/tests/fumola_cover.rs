@@ -0,0 +1,60 @@
+use fumola::check::system_from_exp;
+use fumola::cover::{line_col, Coverage, PointKind, Report};
+use fumola::schedule::ByName;
+use fumola::step::{fully_observed, Fuel};
+
+const PROG: &str = "let box f = {\\x =>
+  switch x {
+    #$a(q) { ret q };
+    #$b(q) { assert q == 1 }
+  }};
+let box g = {\\y => ret y};
+let r = f #$a(1);
+ret r";
+
+fn cover(input: &str) -> Coverage {
+    let e = fumola::parser::ExpParser::new().parse(input).unwrap();
+    let mut sys = system_from_exp(&e).unwrap();
+    let mut cov = Coverage::default();
+    fully_observed(&mut sys, &mut ByName, &Fuel::default(), &mut cov);
+    cov
+}
+
+fn report(cov: &Coverage) -> String {
+    let e = fumola::parser::ExpParser::new().parse(PROG).unwrap();
+    Report::new(PROG, &e, cov).to_string()
+}
+
+#[test]
+fn test_cover_missed() {
+    let cov = cover(PROG);
+    assert_eq!(
+        report(&cov),
+        "coverage: 8/11 expressions, 1/2 cases, 0/0 branches, 1/2 boxes, 0/1 asserts
+never ran:
+  4:14: case #$b(q): assert q == 1
+  4:14: assert: assert q == 1
+  6:14: box g: \\y => ret y
+"
+    );
+}
+
+#[test]
+fn test_cover_merge() {
+    // Same length arguments, so the same locations.
+    let mut cov = cover(PROG);
+    cov.merge(&cover(&PROG.replace("f #$a(1)", "f #$b(1)")));
+    let e = fumola::parser::ExpParser::new().parse(PROG).unwrap();
+    let r = Report::new(PROG, &e, &cov);
+    assert_eq!(r.count(PointKind::Case), (2, 2));
+    assert_eq!(r.count(PointKind::Assert), (1, 1));
+    assert_eq!(r.missed().len(), 1);
+    let j = cov.to_json();
+    assert_eq!(Coverage::from_json(&j), Ok(cov));
+}
+
+#[test]
+fn test_cover_parse_ignores_locations() {
+    fumola::check::parse("ret 1", "Ret(Num(1))").unwrap();
+    assert_eq!(line_col("ab\ncd", 4), (2, 2));
+}
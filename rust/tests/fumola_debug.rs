@@ -0,0 +1,54 @@
+use fumola::ast::Sym;
+use fumola::check::system_from_exp;
+use fumola::debug::{Break, Command, Debugger};
+
+fn session(prog: &str, script: &str) -> String {
+    let e = fumola::parser::ExpParser::new().parse(prog).unwrap();
+    let mut d = Debugger::new(system_from_exp(&e).unwrap());
+    let mut out = vec![];
+    d.session(script.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_debug_parse_commands() {
+    assert_eq!("s p".parse(), Ok(Command::Step(Sym::Id("p".to_string()))));
+    assert_eq!("step %".parse(), Ok(Command::Step(Sym::None)));
+    assert_eq!(
+        "break put x".parse(),
+        Ok(Command::Break(Break::Put(Sym::Id("x".to_string()))))
+    );
+    assert_eq!("break error".parse(), Ok(Command::Break(Break::Error)));
+    assert!("frobnicate".parse::<Command>().is_err());
+}
+
+#[test]
+fn test_debug_break_put() {
+    let out = session(
+        "let p = ~$p { let z = $x := 1; ret 2 }; &p",
+        "break put x\ncontinue\ninspect p\nquit\n",
+    );
+    assert!(out.contains("  p: put x <= 1\nbreakpoint: p put x <= 1\n"));
+    assert!(out.contains("p: running, at ret_ !x\n"));
+    assert!(out.contains("  trace = [put x <= 1]\n"));
+}
+
+#[test]
+fn test_debug_break_error() {
+    let out = session(
+        "let p = ~$p { assert 1 == 2 }; &p",
+        "break error\ncontinue\nprocs\n",
+    );
+    assert!(out.contains("breakpoint: p failed: "));
+    assert!(out.contains("p: error "));
+}
+
+#[test]
+fn test_debug_switch_head_shows_labels() {
+    let out = session(
+        "switch #$a(1) { #$a(q) { ret q }; #$b(q) { ret 2 } }",
+        "round\nprocs\nround\nprocs\n",
+    );
+    assert!(out.contains("%: running, at switch #$a(1) { #$a(q) => __; #$b(q) => __ }\n"));
+    assert!(out.contains("%: running, at ret q\n"));
+}
@@ -0,0 +1,73 @@
+use fumola::ast::step::Event;
+use fumola::effects::{analyze, Effects};
+use fumola::parser::ExpParser;
+
+fn effects(input: &str) -> Effects {
+    analyze(&ExpParser::new().parse(input).unwrap()).unwrap()
+}
+
+const PROG: &str = "let box w = {\\x => \\y => x := y};
+let p = ~$p { #$n { w $a 1 } };
+let q = &p;
+let r = #$m { let k = &$k; @k };
+let s = ~`(ret $q) { $b := 2 };
+@q";
+
+#[test]
+fn test_effects_nests_and_boxes() {
+    assert_eq!(
+        effects(PROG).to_string(),
+        "puts: b, n/a\ngets: k, n/a\nlinks: k\nspawns: p, q\n"
+    );
+    assert_eq!(
+        effects("#$n { #$m { $a := 1 } }").to_string(),
+        "puts: n/m/a\ngets: \nlinks: \nspawns: \n"
+    );
+    // processes are spawned within nests, but do not run within them
+    assert_eq!(
+        effects("#$n { ~$p { $a := 1 } }").to_string(),
+        "puts: a\ngets: \nlinks: \nspawns: n/p\n"
+    );
+}
+
+#[test]
+fn test_effects_unknown_symbols() {
+    let e = effects("let x = @!p; let _ = x := 1; #x { $a := 2 }");
+    assert_eq!(e.to_string(), "puts: *, */a\ngets: p\nlinks: \nspawns: \n");
+    // a recursive call is analyzed with unknown arguments
+    let e = effects("box rec f { \\x => let _ = x := 1; let box g = ret f; g $b }; f $a");
+    assert_eq!(e.to_string(), "puts: *, a\ngets: \nlinks: \nspawns: \n");
+    // ...and nested symbols of a recursion that nests deeper are unknown
+    let e = effects("box rec f { let _ = $a := 1; #$n { let box g = ret f; g } }; f");
+    assert_eq!(e.to_string(), "puts: *, a\ngets: \nlinks: \nspawns: \n");
+}
+
+#[test]
+fn test_effects_cover_runs() {
+    for input in [
+        PROG,
+        "let p = ~$a { $x := 1 }; let q = ~$b { $x := 2 }; let _ = &p; let _ = &q; let v = @!x; assert v == 2",
+        "let box f = {\\x => #$n { $a := x }}; let p = ~$p { f 1 }; let y = f 2; let z = @y; &p",
+        "switch #$a($s) { #$a(x) { #x { $t := 1 } }; #$b(y) { ret y } }",
+        // a box read from the store may use any symbol
+        "let p = $b := {$x := 1}; let box f = @p; f",
+    ] {
+        let e = effects(input);
+        let (sys, _) = fumola::check::run(
+            input,
+            &mut fumola::schedule::ByName,
+            &fumola::step::Fuel::default(),
+        )
+        .unwrap();
+        for stamp in sys.timeline.0.iter() {
+            let (pats, s) = match &stamp.event {
+                Event::Put(s, _) => (&e.puts, s),
+                Event::Get(s, _) | Event::GetWait(s, _) => (&e.gets, s),
+                Event::Link(fumola::ast::Val::Sym(s), _) => (&e.links, s),
+                Event::Spawn(s) => (&e.spawns, s),
+                _ => continue,
+            };
+            assert!(pats.iter().any(|p| p.matches(s)), "{}: {}", input, s);
+        }
+    }
+}
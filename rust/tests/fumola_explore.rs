@@ -0,0 +1,55 @@
+use fumola::check::system_from_exp;
+use fumola::explore::{final_stores, Explorer};
+
+fn initial(input: &str) -> fumola::ast::step::System {
+    let e = fumola::parser::ExpParser::new().parse(input).unwrap();
+    system_from_exp(&e).unwrap()
+}
+
+#[test]
+fn test_explore_race_finals() {
+    let sys = initial("let p = ~$a { $x := 1 }; let q = ~$b { $x := 2 }; let _ = &p; &q");
+    let ex = Explorer::default().explore(&sys);
+    assert!(!ex.truncated);
+    assert!(ex.failures.is_empty());
+    assert_eq!(
+        final_stores(&ex),
+        vec![
+            "[a => ~a; b => ~b; x => 1]".to_string(),
+            "[a => ~a; b => ~b; x => 2]".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_explore_assertion_failure() {
+    let sys = initial(
+        "let p = ~$a { $x := 1 }; let q = ~$b { $x := 2 }; let _ = &p; let _ = &q; let v = @!x; assert v == 2",
+    );
+    let ex = Explorer::default().explore(&sys);
+    assert_eq!(ex.failures.len(), 1);
+    let fail = &ex.failures[0];
+    assert_eq!(format!("{}", fail.error), "assertionFailure(1 == 2)");
+    // Replaying the schedule reproduces the failure.
+    let mut sys = sys;
+    for s in fail.schedule.iter() {
+        fumola::step::one(&mut sys, s).unwrap();
+    }
+    assert!(matches!(
+        sys.procs.0[&fail.proc],
+        fumola::ast::step::Proc::Error(_, _)
+    ));
+}
+
+#[test]
+fn test_explore_reduction() {
+    let input = "let p = ~$a { let y = ret 1; let z = ret y; $x := z }; let q = ~$b { let y = ret 2; $x := y }; let _ = &p; &q";
+    let full = Explorer {
+        reduce: false,
+        ..Explorer::default()
+    }
+    .explore(&initial(input));
+    let reduced = Explorer::default().explore(&initial(input));
+    assert_eq!(final_stores(&full), final_stores(&reduced));
+    assert!(reduced.states < full.states);
+}
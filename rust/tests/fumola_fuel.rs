@@ -0,0 +1,64 @@
+use fumola::ast::step::{Limit, Proc};
+use fumola::check::run;
+use fumola::schedule::ByName;
+use fumola::step::{Fuel, Stop};
+
+const LOOP: &str = "box rec f { let box g = ret f; g }; f";
+
+#[test]
+fn test_fuel_global_steps() {
+    let fuel = Fuel {
+        steps: Some(50),
+        ..Fuel::default()
+    };
+    let (sys, stop) = run(LOOP, &mut ByName, &fuel).unwrap();
+    assert_eq!(stop, Stop::OutOfFuel(Limit::Steps(50)));
+    assert_eq!(sys.step, 50);
+    match &sys.procs.0[&fumola::ast::Sym::None] {
+        Proc::Error(_, e) => assert_eq!(format!("{}", e), "outOfFuel(steps(50))"),
+        p => panic!("unexpected process {}", p),
+    }
+}
+
+#[test]
+fn test_fuel_proc_steps() {
+    let fuel = Fuel {
+        proc_steps: Some(20),
+        ..Fuel::default()
+    };
+    let (sys, stop) = run(
+        &format!("let _ = ~$p {{ {} }}; ret 0", LOOP),
+        &mut ByName,
+        &fuel,
+    )
+    .unwrap();
+    assert_eq!(stop, Stop::Quiescent);
+    assert_eq!(sys.proc_steps[&fumola::ast::Sym::Id("p".to_string())], 20);
+    let procs = format!("{}", sys.procs);
+    assert!(procs.starts_with("[% => halted([ret 0]); p => error(outOfFuel(procSteps(20)), "));
+}
+
+#[test]
+fn test_fuel_time() {
+    let fuel = Fuel {
+        time: Some(std::time::Duration::from_millis(0)),
+        ..Fuel::default()
+    };
+    let (sys, stop) = run(LOOP, &mut ByName, &fuel).unwrap();
+    assert_eq!(
+        stop,
+        Stop::OutOfFuel(Limit::Time(std::time::Duration::from_millis(0)))
+    );
+    assert_eq!(sys.step, 0);
+}
+
+#[test]
+fn test_fuel_unused() {
+    let fuel = Fuel {
+        steps: Some(1000),
+        proc_steps: Some(1000),
+        ..Fuel::default()
+    };
+    let (_, stop) = run("let p = ~$p { ret 42 }; &p", &mut ByName, &fuel).unwrap();
+    assert_eq!(stop, Stop::Quiescent);
+}
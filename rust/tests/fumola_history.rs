@@ -0,0 +1,67 @@
+use fumola::ast::{Sym, Val};
+use fumola::check::{system, system_from_exp};
+use fumola::history::History;
+use fumola::parser::ExpParser;
+use fumola::step::fully;
+
+fn id(s: &str) -> Sym {
+    Sym::Id(s.to_string())
+}
+
+#[test]
+fn test_history_versions() {
+    let sys = system("let _ = $a := 1; let _ = ~$p { $a := 2 }; let _ = #$n { $a := 3 }; $a := 4")
+        .unwrap();
+    let h = History::of(&sys);
+    assert_eq!(
+        format!("{}", h),
+        "a\n  step 2 by %: 1\n  step 6 by p: 2\n  step 12 by %: 4\n\
+         p\n  step 5 by %: ~p\n\
+         n/a\n  step 9 by %: 3\n"
+    );
+    assert_eq!(h.symbol(&id("a")).len(), 3);
+    assert!(h.symbol(&id("b")).is_empty());
+}
+
+#[test]
+fn test_history_as_of_and_diff() {
+    let sys = system("let _ = $a := 1; let _ = $b := 1; $a := 2").unwrap();
+    let h = History::of(&sys);
+    assert_eq!(format!("{}", h.as_of(0)), "[]");
+    assert_eq!(format!("{}", h.as_of(3)), "[a => 1]");
+    assert_eq!(format!("{}", h.as_of(sys.step)), format!("{}", sys.store));
+    assert_eq!(h.read(&id("a"), 5), Some(&Val::Num(1)));
+    let diff: Vec<String> = h.diff(3, sys.step).iter().map(|c| c.to_string()).collect();
+    assert_eq!(diff, vec!["~ a: 1 => 2", "+ b => 1"]);
+    let diff: Vec<String> = h.diff(sys.step, 0).iter().map(|c| c.to_string()).collect();
+    assert_eq!(diff, vec!["- a => 2", "- b => 1"]);
+}
+
+#[test]
+fn test_history_host_inputs() {
+    let e = ExpParser::new().parse("let x = @!i; $o := x").unwrap();
+    let mut sys = system_from_exp(&e).unwrap();
+    sys.store.0.insert(id("i"), Val::Num(7));
+    fully(&mut sys);
+    let h = History::of(&sys);
+    assert_eq!(h.symbol(&id("i"))[0].proc, None);
+    assert_eq!(format!("{}", h.as_of(0)), "[i => 7]");
+    assert_eq!(format!("{}", h.as_of(sys.step)), "[i => 7; o => 7]");
+}
+
+#[test]
+fn test_history_host_input_overwritten() {
+    let e = ExpParser::new().parse("let x = @!i; $i := 8").unwrap();
+    let mut sys = system_from_exp(&e).unwrap();
+    sys.store.0.insert(id("i"), Val::Num(7));
+    fully(&mut sys);
+    let h = History::of(&sys);
+    let vs = h.symbol(&id("i"));
+    assert_eq!(vs.len(), 2);
+    assert_eq!((vs[0].proc.clone(), &vs[0].value), (None, &Val::Num(7)));
+    assert_eq!(vs[1].proc, Some(Sym::None));
+    assert_eq!(format!("{}", h.as_of(0)), "[i => 7]");
+    assert_eq!(format!("{}", h.as_of(sys.step)), "[i => 8]");
+    let diff: Vec<String> = h.diff(0, sys.step).iter().map(|c| c.to_string()).collect();
+    assert_eq!(diff, vec!["~ i: 7 => 8"]);
+}
@@ -0,0 +1,28 @@
+use fumola::check::system;
+
+#[test]
+fn test_html_get_links_to_put() {
+    let sys = system("let x = #$n{ $a := 3 }; @x").unwrap();
+    let html = fumola::html::system(&sys);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<details open class=\"nest\"><summary>#n</summary>"));
+    assert!(html.contains("<li class=\"put\" id=\"put-0\">put n/a &lt;= 3</li>"));
+    assert!(html.contains("<li class=\"get\">get <a href=\"#put-0\">n/a</a> =&gt; 3</li>"));
+    assert!(html.contains("<tr><td><a href=\"#put-0\">n/a</a></td><td>3</td></tr>"));
+}
+
+#[test]
+fn test_html_link_to_halted_proc() {
+    let sys = system("let p = ~$p { ret 42 }; &p").unwrap();
+    let html = fumola::html::system(&sys);
+    assert!(html.contains("id=\"proc-1\"><summary>p <span class=\"status-halted\">"));
+    assert!(html.contains("<li class=\"link\">link <a href=\"#proc-1\">~p</a> =&gt; 42</li>"));
+}
+
+#[test]
+fn test_html_unfinished_nest() {
+    let sys = system("#$n { &$s }").unwrap();
+    let html = fumola::html::system(&sys);
+    assert!(html.contains("waiting for pointer s"));
+    assert!(html.contains("#n <span class=\"open\">(unfinished)</span>"));
+}
@@ -0,0 +1,95 @@
+use fumola::ast::{
+    step::{Event, StorePolicy, System},
+    Sym, Val,
+};
+use fumola::check::initial_system;
+use fumola::incr::Engine;
+use fumola::schedule::{ByName, Rng};
+use fumola::step::{fully, Fuel};
+
+const PROGRAM: &str = "\
+let a = #$x { let v = @!i; let _ = $o := v; ret v };
+let b = #$y {
+  let w = @!j;
+  let c = #$z { let u = @!k; ret [$u => u] };
+  ret [$w => w; $c => c]
+};
+let d = #$d { let p = ~$p { ret 1 }; &p };
+ret [$a => a; $b => b; $d => d]";
+
+/// The program's system, with the given inputs in its store.
+fn system(inputs: &[(&str, i32)]) -> System {
+    let mut sys = initial_system(PROGRAM, StorePolicy::default()).unwrap();
+    for (s, n) in inputs.iter() {
+        sys.store.0.insert(Sym::Id(s.to_string()), Val::Num(*n));
+    }
+    sys
+}
+
+/// The store and traces of a system, and the puts and gets of its
+/// timeline, in order (but without their steps: reused nests take none).
+fn outcome(sys: &System) -> String {
+    let mut out = format!("{}", sys);
+    for st in sys.timeline.0.iter() {
+        if let Event::Put(_, _) | Event::Get(_, _) | Event::GetWait(_, _) = st.event {
+            out.push_str(&format!("{}: {}\n", st.proc, st.event))
+        }
+    }
+    out
+}
+
+fn incremental(engine: &mut Engine, inputs: &[(&str, i32)]) -> String {
+    let mut sys = system(inputs);
+    engine.run(&mut sys, &mut ByName, &Fuel::default());
+    outcome(&sys)
+}
+
+fn from_scratch(inputs: &[(&str, i32)]) -> String {
+    let mut sys = system(inputs);
+    fully(&mut sys);
+    outcome(&sys)
+}
+
+#[test]
+fn test_incr_reuses_unaffected_nests() {
+    let mut engine = Engine::new();
+    incremental(&mut engine, &[("i", 1), ("j", 2), ("k", 3)]);
+    // x, y, z and d run; d spawns, so it is not recorded
+    assert_eq!((engine.ran, engine.reused), (4, 0));
+    assert!(!engine.cache.contains_key(&Sym::Id("d".to_string())));
+
+    // nothing changed: x and y are reused, without entering z
+    incremental(&mut engine, &[("i", 1), ("j", 2), ("k", 3)]);
+    assert_eq!((engine.ran, engine.reused), (5, 2));
+
+    // only k changed: y runs again, but not x
+    incremental(&mut engine, &[("i", 1), ("j", 2), ("k", 4)]);
+    assert_eq!((engine.ran, engine.reused), (8, 3));
+}
+
+#[test]
+fn test_incr_reruns_changed_nest() {
+    let mut engine = Engine::new();
+    incremental(&mut engine, &[("i", 1), ("j", 2), ("k", 3)]);
+    let out = incremental(&mut engine, &[("i", 5), ("j", 2), ("k", 3)]);
+    assert_eq!(out, from_scratch(&[("i", 5), ("j", 2), ("k", 3)]));
+    // x runs again, with its new put
+    assert_eq!((engine.ran, engine.reused), (6, 1));
+    assert!(out.contains("x/o => 5"), "{}", out);
+}
+
+#[test]
+fn test_incr_randomized_edits() {
+    let mut rng = Rng(7);
+    let mut engine = Engine::new();
+    let mut inputs = vec![("i", 0), ("j", 0), ("k", 0)];
+    for _ in 0..30 {
+        let edits = 1 + rng.below(2);
+        for _ in 0..edits {
+            let i = rng.below(inputs.len());
+            inputs[i].1 = rng.below(3) as i32;
+        }
+        assert_eq!(incremental(&mut engine, &inputs), from_scratch(&inputs));
+    }
+    assert!(engine.reused > 0);
+}
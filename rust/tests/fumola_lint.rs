@@ -0,0 +1,90 @@
+use fumola::lint::{allow_at, check, report, AllowAt, Lint};
+use fumola::parser::ExpParser;
+
+use std::collections::BTreeSet;
+
+fn lint(input: &str, allow: &[Lint]) -> String {
+    let e = ExpParser::new().parse(input).unwrap();
+    let allow: BTreeSet<Lint> = allow.iter().copied().collect();
+    report(input, &check(&e, &allow).unwrap())
+}
+
+const PROG: &str = "let x = ret 1;
+let y = ret 2;
+box f { \\y => ret y };
+let z = ret [$a => 1; $b => 2; $a => 3];
+let _ = switch #$a(y) { #$a(w) { ret w }; #$b(w) { ret w }; #$a(v) { ret v } };
+let _ = assert 1 == 2;
+let y = $c := z;
+let _ = @!q;
+@y";
+
+#[test]
+fn test_lint_findings() {
+    assert_eq!(
+        lint(PROG, &[]),
+        "1:1: warning[unused-let]: variable x is never used\n\
+         3:1: warning[unused-box]: box f is never used\n\
+         4:9: error[duplicate-label]: field $a is never read: an earlier field has its label\n\
+         5:52: error[unreachable-case]: case #$b never matches: the value has label $a\n\
+         5:70: error[unreachable-case]: case #$a never matches: an earlier case has its label\n\
+         6:9: warning[literal-assert]: assertion on literals always fails\n\
+         7:1: warning[shadowed]: value variable y shadows an earlier binding\n\
+         8:9: warning[get-never-put]: get of q, which is never put\n"
+    );
+}
+
+#[test]
+fn test_lint_allow() {
+    assert_eq!(
+        lint(
+            PROG,
+            &[
+                Lint::UnusedLet,
+                Lint::UnusedBox,
+                Lint::Shadowed,
+                Lint::UnreachableCase,
+                Lint::DuplicateLabel,
+                Lint::GetNeverPut,
+                Lint::LiteralAssert
+            ]
+        ),
+        ""
+    );
+    assert_eq!("get-never-put".parse(), Ok(Lint::GetNeverPut));
+    assert!("unused".parse::<Lint>().is_err());
+}
+
+#[test]
+fn test_lint_clean() {
+    for input in [
+        // box code sees none of its context's variables
+        "let y = ret 1; box f { \\y => ret y }; f y",
+        "box rec f { \\x => switch x { #$a(n) { ret n }; #$b(m) { let box g = ret f; g #$a(m) } } }; f #$b(1)",
+        "let p = ~$p { $a := 1 }; let _ = &p; let a = &$a; @a",
+        "let r = ret [$a => 1; $b => 2]; let [$a => x] = ret r; assert x == 1",
+    ] {
+        assert_eq!(lint(input, &[]), "", "{}", input);
+    }
+}
+
+#[test]
+fn test_lint_allow_at() {
+    let e = ExpParser::new().parse(PROG).unwrap();
+    let mut findings = check(&e, &BTreeSet::new()).unwrap();
+    let allow: Vec<AllowAt> = ["get-never-put@8", "unused-let@7", "unused-let@1"]
+        .iter()
+        .map(|a| a.parse().unwrap())
+        .collect();
+    allow_at(PROG, &mut findings, &allow);
+    let out = report(PROG, &findings);
+    assert!(
+        !out.contains("get-never-put") && !out.contains("1:1:"),
+        "{}",
+        out
+    );
+    // other lints at an allowed line remain
+    assert!(out.contains("7:1: warning[shadowed]"), "{}", out);
+    assert!("get-never-put".parse::<AllowAt>().is_err());
+    assert!("get-never-put@x".parse::<AllowAt>().is_err());
+}
@@ -0,0 +1,145 @@
+use fumola::lsp::{read_message, write_message, Server};
+use serde_json::{json, Value};
+
+const URI: &str = "file:///test.fum";
+
+const PROG: &str = "box f { \\x => #$n { $a := x } };
+let y = ret 1;
+let p = f y;
+@p";
+
+/// Run a session over the messages, returning the server's messages.
+fn session(msgs: &[Value]) -> Vec<Value> {
+    let mut input = vec![];
+    for msg in msgs.iter() {
+        write_message(&mut input, msg).unwrap();
+    }
+    let mut out = vec![];
+    Server::new()
+        .session(std::io::Cursor::new(input), &mut out)
+        .unwrap();
+    let mut out = std::io::Cursor::new(out);
+    let mut replies = vec![];
+    while let Some(msg) = read_message(&mut out).unwrap() {
+        replies.push(msg)
+    }
+    replies
+}
+
+fn open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": URI, "languageId": "fumola", "version": 1, "text": text } },
+    })
+}
+
+fn request(id: i64, method: &str, line: usize, character: usize) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    })
+}
+
+fn pos(line: usize, character: usize) -> Value {
+    json!({ "line": line, "character": character })
+}
+
+#[test]
+fn test_lsp_diagnostics() {
+    let replies = session(&[
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }),
+        open("let x = ret 1;\nret y"),
+        open("let x = ret 1;\nret"),
+    ]);
+    assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+    let unbound = &replies[1]["params"]["diagnostics"];
+    assert_eq!(unbound[0]["message"], "unbound value variable y");
+    assert_eq!(unbound[0]["range"]["start"], pos(1, 4));
+    assert_eq!(unbound[0]["range"]["end"], pos(1, 5));
+    let parse = &replies[2]["params"]["diagnostics"];
+    assert_eq!(parse.as_array().unwrap().len(), 1);
+    assert_eq!(parse[0]["range"]["start"], pos(1, 3));
+}
+
+#[test]
+fn test_lsp_definition_hover_completion() {
+    let replies = session(&[
+        open(PROG),
+        // `f` in `f y`, and `y` there
+        request(1, "textDocument/definition", 2, 8),
+        request(2, "textDocument/definition", 2, 10),
+        request(3, "textDocument/hover", 2, 8),
+        request(4, "textDocument/hover", 0, 21),
+        request(5, "textDocument/completion", 3, 1),
+        request(6, "textDocument/hover", 1, 4),
+    ]);
+    assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+    assert_eq!(replies[1]["result"]["range"]["start"], pos(0, 4));
+    assert_eq!(replies[2]["result"]["range"]["start"], pos(1, 4));
+    assert_eq!(
+        replies[3]["result"]["contents"]["value"],
+        "```\nbox f { \\x => #$n { $a := x } }\n```"
+    );
+    assert_eq!(
+        replies[4]["result"]["contents"]["value"],
+        "symbol `$a`\n\n- put `n/a` at 1:21\n- get `n/a` at 4:1"
+    );
+    let labels: Vec<_> = replies[5]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(labels, ["p", "y", "f"]);
+    // no hover on values
+    assert_eq!(replies[6]["result"], Value::Null);
+}
+
+#[test]
+fn test_lsp_formatting() {
+    let replies = session(&[
+        open("let x = ret 1; #$n { let y = ret x; $a := y }"),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "textDocument/formatting",
+            "params": { "textDocument": { "uri": URI }, "options": { "tabSize": 2, "insertSpaces": true } },
+        }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/rename", "params": {} }),
+    ]);
+    assert_eq!(
+        replies[1]["result"][0]["newText"],
+        "let x = ret 1;\n#$n {\n  let y = ret x;\n  $a := y\n}\n"
+    );
+    assert_eq!(replies[2]["error"]["code"], -32601);
+}
+
+#[test]
+fn test_lsp_exit_code() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let mut input = vec![];
+    for msg in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ] {
+        write_message(&mut input, &msg).unwrap();
+    }
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fumola"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    let reply = read_message(&mut std::io::Cursor::new(out.stdout)).unwrap();
+    assert_eq!(reply.unwrap()["result"], Value::Null);
+}
@@ -0,0 +1,91 @@
+use fumola::ast::{
+    step::{Env, Event, Store, StorePolicy, ValsEnv},
+    BxesEnv, Sym, Val,
+};
+use fumola::check::initial_system;
+use fumola::incr::Engine;
+use fumola::memo::{Memo, NestRun};
+use fumola::parser::ExpParser;
+use fumola::schedule::ByName;
+use fumola::step::{fully, Fuel};
+
+use std::collections::HashMap;
+
+const PROGRAM: &str = "\
+let box f = {\\x => #$n { let v = @!i; let _ = $o := v; ret [$x => x; $v => v] }};
+let _ = $i := 1;
+let a = f 1;
+let b = f 2;
+let c = f 1;
+ret [$a => a; $b => b; $c => c]";
+
+fn run(engine: &mut Engine) -> String {
+    let mut sys = initial_system(PROGRAM, StorePolicy::default()).unwrap();
+    engine.run(&mut sys, &mut ByName, &Fuel::default());
+    format!("{}", sys)
+}
+
+fn id(s: &str) -> Sym {
+    Sym::Id(s.to_string())
+}
+
+#[test]
+fn test_memo_replays_repeated_nest() {
+    let mut engine = Engine::new();
+    let out = run(&mut engine);
+    let mut sys = initial_system(PROGRAM, StorePolicy::default()).unwrap();
+    fully(&mut sys);
+    assert_eq!(out, format!("{}", sys));
+    // the third call replays the first; the second has another argument
+    assert_eq!((engine.ran, engine.reused), (2, 1));
+    assert_eq!(engine.cache.len(), 2);
+}
+
+#[test]
+fn test_memo_inspect_and_clear() {
+    let mut engine = Engine::new();
+    run(&mut engine);
+    let table = format!("{}", engine.cache);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", table);
+    for line in lines.iter() {
+        assert!(line.starts_with("n "), "{}", line);
+        assert!(line.ends_with("; puts n/o := 1"), "{}", line);
+    }
+
+    // a second run replays every call
+    run(&mut engine);
+    assert_eq!((engine.ran, engine.reused), (2, 4));
+
+    engine.cache.clear();
+    assert!(engine.cache.is_empty());
+    run(&mut engine);
+    assert_eq!((engine.ran, engine.reused), (4, 5));
+}
+
+#[test]
+fn test_memo_lookup_checks_reads() {
+    let body = ExpParser::new().parse("let v = @!i; $o := v").unwrap();
+    let mut env = Env {
+        vals: ValsEnv(HashMap::new()),
+        bxes: BxesEnv(HashMap::new()),
+    };
+    let events = vec![
+        Event::Get(id("i"), Val::Num(1)),
+        Event::Put(id("o"), Val::Num(1)),
+    ];
+    let mut memo = Memo::new();
+    let run = NestRun::new(&env, body.clone(), events, vec![], Val::Ptr(id("o")));
+    memo.insert(id("n"), run);
+
+    let mut store = Store(HashMap::new());
+    assert!(memo.lookup(&id("n"), &env, &body, &store).is_none());
+    store.0.insert(id("i"), Val::Num(1));
+    assert!(memo.lookup(&id("n"), &env, &body, &store).is_some());
+    // variables that the body does not use do not matter
+    env.vals.0.insert("w".to_string(), Val::Num(3));
+    assert!(memo.lookup(&id("n"), &env, &body, &store).is_some());
+    store.0.insert(id("i"), Val::Num(2));
+    assert!(memo.lookup(&id("n"), &env, &body, &store).is_none());
+    assert!(memo.lookup(&id("m"), &env, &body, &store).is_none());
+}
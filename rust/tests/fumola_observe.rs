@@ -0,0 +1,80 @@
+use fumola::check::system_from_exp;
+use fumola::observe::{Collect, JsonLines, Kind};
+use fumola::schedule::ByName;
+use fumola::step::{fully_observed, Fuel};
+
+fn observe(input: &str, obs: &mut dyn fumola::observe::StepObserver) {
+    let e = fumola::parser::ExpParser::new().parse(input).unwrap();
+    let mut sys = system_from_exp(&e).unwrap();
+    fully_observed(&mut sys, &mut ByName, &Fuel::default(), obs);
+}
+
+#[test]
+fn test_observe_collect() {
+    let mut obs = Collect::default();
+    observe("let p = ~$p { $a := 5 }; let x = @!a; &p", &mut obs);
+    let events: Vec<String> = obs
+        .0
+        .iter()
+        .filter(|e| !matches!(e.kind, Kind::Stepped { .. }))
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "[2] %: spawn p",
+            "[3] p: put a <= 5",
+            "[4] p: halt !a",
+            "[5] %: get a => 5",
+            "[7] %: wait for process p",
+            "[8] %: link ~p => !a",
+            "[9] %: halt !a",
+        ]
+    );
+    assert!(obs
+        .0
+        .iter()
+        .any(|e| e.to_string() == "[0] %: step let p = __; __"));
+}
+
+#[test]
+fn test_observe_error_and_wait() {
+    let mut obs = Collect::default();
+    observe(
+        "let p = ~$p { assert 1 == 2 }; let q = ~$q { &$s }; &p",
+        &mut obs,
+    );
+    let events: Vec<String> = obs
+        .0
+        .iter()
+        .filter(|e| {
+            matches!(
+                e.kind,
+                Kind::Error(_) | Kind::WaitPtr(_) | Kind::WaitHalt(_)
+            )
+        })
+        .map(|e| format!("{}: {}", e.proc, e))
+        .collect();
+    assert_eq!(events.len(), 3);
+    assert!(events
+        .iter()
+        .any(|e| e.contains("p: error assertionFailure(1 == 2)")));
+    assert!(events.iter().any(|e| e.contains("q: wait for pointer s")));
+    assert!(events.iter().any(|e| e.contains("%: wait for process p")));
+}
+
+#[test]
+fn test_observe_json_lines() {
+    let mut obs = JsonLines::new(vec![]);
+    observe("$a := 5", &mut obs);
+    let out = String::from_utf8(obs.out).unwrap();
+    let put: Vec<serde_json::Value> = out
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .filter(|v: &serde_json::Value| v["event"] == "put")
+        .collect();
+    assert_eq!(
+        put,
+        vec![serde_json::json!({"event": "put", "proc": "%", "step": 1, "sym": "a", "val": "5"})]
+    );
+}
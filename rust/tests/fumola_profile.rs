@@ -0,0 +1,53 @@
+use fumola::ast::Sym;
+use fumola::check::system_from_exp;
+use fumola::profile::{namespace, Profile};
+use fumola::schedule::ByName;
+use fumola::step::{fully_observed, Fuel};
+
+fn profile(input: &str) -> Profile {
+    let e = fumola::parser::ExpParser::new().parse(input).unwrap();
+    let mut sys = system_from_exp(&e).unwrap();
+    let mut prof = Profile::default();
+    fully_observed(&mut sys, &mut ByName, &Fuel::default(), &mut prof);
+    prof
+}
+
+const PROG: &str =
+    "let box f = {\\x => #$n { $a := x }}; let p = ~$p { f 1 }; let y = f 2; let z = @y; &p";
+
+#[test]
+fn test_profile_counts() {
+    let prof = profile(PROG);
+    assert_eq!(prof.steps, 27);
+    assert_eq!(prof.proc_steps[&Sym::None], 20);
+    assert_eq!(prof.proc_steps[&Sym::Id("p".to_string())], 7);
+    assert_eq!(prof.form_steps["extract"], 2);
+    assert_eq!(prof.box_calls["f"], 2);
+    assert_eq!(prof.box_steps["f"], 10);
+    assert_eq!(prof.max_depth, 2);
+    let n = Some(Sym::Id("n".to_string()));
+    assert_eq!(prof.writes[&n], 2);
+    assert_eq!(prof.reads[&n], 1);
+}
+
+#[test]
+fn test_profile_folded() {
+    let folded = profile(PROG).folded();
+    assert!(folded.contains("%;box f;#n;put 1\n"));
+    assert!(folded.contains("p;box f;lambda 1\n"));
+    let total: usize = folded
+        .lines()
+        .map(|l| l.rsplit(' ').next().unwrap().parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(total, 27);
+}
+
+#[test]
+fn test_profile_namespace() {
+    let id = |s: &str| Box::new(Sym::Id(s.to_string()));
+    assert_eq!(namespace(&Sym::Id("a".to_string())), None);
+    assert_eq!(
+        namespace(&Sym::Nest(id("m"), Box::new(Sym::Nest(id("n"), id("a"))))),
+        Some(Sym::Nest(id("m"), id("n")))
+    );
+}
@@ -0,0 +1,57 @@
+use fumola::check::system;
+use fumola::quiesce::{report, Cause};
+
+#[test]
+fn test_quiesce_all_halted() {
+    let r = report(&system("let p = ~$p { ret 42 }; &p").unwrap());
+    assert!(r.quiescent && r.all_halted());
+    assert_eq!(
+        format!("{}", r),
+        "quiescent: 2 halted, 0 failed, 0 waiting\n"
+    );
+}
+
+#[test]
+fn test_quiesce_never_put() {
+    let r = report(&system("let p = ~$p { &$s }; &p").unwrap());
+    assert!(r.quiescent && !r.all_halted());
+    assert!(matches!(&r.stuck[1].cause, Cause::NeverPut(_)));
+    assert_eq!(
+        format!("{}", r),
+        "quiescent: 0 halted, 0 failed, 2 waiting
+  % waits for p, which waits for pointer s, which is never put
+  p waits for pointer s, which is never put
+"
+    );
+}
+
+#[test]
+fn test_quiesce_errored() {
+    let r = report(&system("let p = ~$p { assert 1 == 2 }; &p").unwrap());
+    assert_eq!(
+        format!("{}", r),
+        "quiescent: 0 halted, 1 failed, 1 waiting
+  p failed: assertionFailure(1 == 2)
+  % waits for p, which failed: assertionFailure(1 == 2)
+"
+    );
+}
+
+#[test]
+fn test_quiesce_cycle() {
+    let r = report(
+        &system(
+            "let _ = ~$a { let _ = assert 0 == 0; &~b }; let _ = ~$b { &~a }; let _ = ~$c { &~a }; ret 0",
+        ).unwrap(),
+    );
+    assert_eq!(r.cycles.len(), 1);
+    assert_eq!(
+        format!("{}", r),
+        "quiescent: 1 halted, 0 failed, 3 waiting
+  wait cycle: a -> b -> a
+  a waits in cycle a -> b -> a
+  b waits in cycle a -> b -> a
+  c waits for a, which waits in cycle a -> b -> a
+"
+    );
+}
@@ -0,0 +1,36 @@
+use fumola::check::system;
+use fumola::race::{detect, report, Kind};
+
+#[test]
+fn test_race_write_write() {
+    let sys =
+        system("let _ = ~$a { let _ = assert 0 == 0; $x := 1 }; let _ = ~$b { $x := 2 }; ret 0")
+            .unwrap();
+    let races = detect(&sys);
+    assert_eq!(races.len(), 1);
+    assert_eq!(
+        format!("{}", races[0]),
+        "race on x: write by a at step 6 (1) and write by b at step 6 (2)"
+    );
+    assert_eq!(
+        report(&sys, &races[0]),
+        "race on x: write by a at step 6 (1) and write by b at step 6 (2)\n  a: [put x <= 1]\n  b: [overwrite x: 1 => 2; put x <= 2]\n"
+    );
+}
+
+#[test]
+fn test_race_read_write() {
+    let sys = system("let _ = $x := 0; let _ = ~$a { $x := 1 }; @!x").unwrap();
+    let races = detect(&sys);
+    assert_eq!(races.len(), 1);
+    let kinds = (races[0].first.kind, races[0].second.kind);
+    assert!(kinds == (Kind::Read, Kind::Write) || kinds == (Kind::Write, Kind::Read));
+}
+
+#[test]
+fn test_race_ordered_by_links() {
+    let sys = system("let p = ~$a { $x := 1 }; let _ = &p; $x := 2").unwrap();
+    assert!(detect(&sys).is_empty());
+    let sys = system("let _ = ~$a { $x := 1 }; let y = &$x; @y").unwrap();
+    assert!(detect(&sys).is_empty());
+}
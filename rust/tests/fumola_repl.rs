@@ -0,0 +1,104 @@
+use fumola::ast::Val;
+use fumola::repl::{Outcome, Repl};
+
+fn value(repl: &mut Repl, line: &str) -> Val {
+    match repl.eval(line).unwrap() {
+        Outcome::Value(v) => v,
+        o => panic!("{}: {:?}", line, o),
+    }
+}
+
+#[test]
+fn test_repl_definitions_persist() {
+    let mut repl = Repl::new();
+    assert_eq!(
+        value(&mut repl, "let x = ret 1;"),
+        Val::Record(fumola::ast::RecordVal(vec![]))
+    );
+    assert_eq!(
+        value(&mut repl, "let box f = {\\y => ret y};").to_string(),
+        "[]"
+    );
+    assert_eq!(value(&mut repl, "f x").to_string(), "1");
+    assert_eq!(format!("{}", repl.env.vals), "[x => 1]");
+}
+
+#[test]
+fn test_repl_store_and_procs_persist() {
+    let mut repl = Repl::new();
+    value(&mut repl, "let p = ~$p { $a := 5 };");
+    assert_eq!(value(&mut repl, "@!a").to_string(), "5");
+    assert_eq!(value(&mut repl, "&p").to_string(), "!a");
+    assert_eq!(value(&mut repl, "#$n { $b := 2 }").to_string(), "!n/b");
+    assert_eq!(format!("{}", repl.trace), "[#n {put n/b <= 2}]");
+    assert_eq!(repl.sys.store.0.len(), 3);
+    assert_eq!(
+        repl.sys.store.0[&fumola::ast::Sym::Id("a".to_string())].to_string(),
+        "5"
+    );
+}
+
+#[test]
+fn test_repl_error_keeps_env() {
+    let mut repl = Repl::new();
+    value(&mut repl, "let x = ret 1;");
+    assert!(matches!(
+        repl.eval("assert x == 2").unwrap(),
+        Outcome::Error(_)
+    ));
+    assert_eq!(value(&mut repl, "ret x").to_string(), "1");
+    assert!(repl.eval("let = ;").is_err());
+}
+
+#[test]
+fn test_repl_session() {
+    let mut repl = Repl::new();
+    let mut out = vec![];
+    repl.session(
+        ":spawn $c := 3\nlet y = @!c;\n:env\n:procs\n:quit\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "fumola> spawned ~0
+fumola> []
+fumola> vals = [y => 3]
+bxes = []
+fumola> % => running, at ret []
+0 => halted with !c
+fumola> "
+    );
+}
+
+#[test]
+fn test_repl_spawn_skips_stored_names() {
+    let mut repl = Repl::new();
+    value(&mut repl, "$0 := 7");
+    let s = repl.spawn("$c := 3").unwrap();
+    assert_eq!(s.to_string(), "1");
+    assert_eq!(value(&mut repl, "@!0").to_string(), "7");
+}
+
+#[test]
+fn test_repl_global_steps() {
+    // as in `run`, a system step steps every process once
+    let mut repl = Repl::new();
+    value(
+        &mut repl,
+        "let p = ~$p { let _ = $a := 1; $b := 2 }; let _ = $c := 3; $d := 4",
+    );
+    let proc_steps: usize = repl.sys.proc_steps.values().sum();
+    assert!(
+        repl.sys.step < proc_steps,
+        "{} {}",
+        repl.sys.step,
+        proc_steps
+    );
+    // the limit counts system steps
+    repl.max_steps = 2;
+    assert!(matches!(
+        repl.eval("let x = ret 1; let y = ret 2; ret 3").unwrap(),
+        Outcome::OutOfFuel
+    ));
+}
@@ -0,0 +1,58 @@
+use fumola::check::{run, RunError};
+use fumola::quiesce::{report, Outcome};
+use fumola::schedule::ByName;
+use fumola::step::Fuel;
+
+fn outcome(input: &str, fuel: &Fuel) -> Outcome {
+    let (sys, stop) = run(input, &mut ByName, fuel).unwrap();
+    Outcome::of(&stop, &report(&sys))
+}
+
+#[test]
+fn test_run_outcomes() {
+    let fuel = Fuel::default();
+    assert_eq!(outcome("let p = ~$p { ret 1 }; &p", &fuel), Outcome::Halted);
+    assert_eq!(outcome("assert 1 == 2", &fuel), Outcome::Errored);
+    assert_eq!(outcome("let p = ~$p { &$s }; &p", &fuel), Outcome::Waiting);
+    let fuel = Fuel {
+        steps: Some(1),
+        ..Fuel::default()
+    };
+    assert_eq!(outcome("let x = ret 1; ret x", &fuel), Outcome::OutOfFuel);
+    assert!(matches!(
+        run("let", &mut ByName, &fuel),
+        Err(RunError::Parse(_))
+    ));
+}
+
+#[test]
+fn test_run_exit_codes() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let status = |input: &str, args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fumola"))
+            .arg("run")
+            .args(args)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait().unwrap().code()
+    };
+    assert_eq!(status("ret 1", &[]), Some(0));
+    assert_eq!(status("assert 1 == 2", &["--format", "store"]), Some(3));
+    assert_eq!(status("&$s", &[]), Some(4));
+    assert_eq!(
+        status("let x = ret 1; ret x", &["--max-steps", "1"]),
+        Some(5)
+    );
+    assert_eq!(status("let", &[]), Some(1));
+}
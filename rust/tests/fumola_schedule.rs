@@ -0,0 +1,65 @@
+use fumola::ast::{step::Procs, Sym};
+use fumola::check::system_with;
+use fumola::schedule::{ByName, Policy, Priority, Random, RoundRobin, Scheduler};
+
+/// Processes a and b put to x during the same system step.
+const RACE: &str = "let _ = ~$a { let _ = assert 0 == 0; $x := 1 }; let _ = ~$b { $x := 2 }; ret 0";
+
+fn final_store(sched: &mut dyn Scheduler) -> String {
+    format!("{}", system_with(RACE, sched).unwrap().store)
+}
+
+fn sym(s: &str) -> Sym {
+    Sym::Id(s.to_string())
+}
+
+#[test]
+fn test_schedule_by_name() {
+    assert_eq!(final_store(&mut ByName), "[a => ~a; b => ~b; x => 2]");
+}
+
+#[test]
+fn test_schedule_priority() {
+    let mut sched = Priority {
+        priorities: vec![(sym("b"), 1)].into_iter().collect(),
+    };
+    assert_eq!(final_store(&mut sched), "[a => ~a; b => ~b; x => 1]");
+}
+
+#[test]
+fn test_schedule_random_is_seeded() {
+    for seed in 0..8 {
+        assert_eq!(
+            final_store(&mut Random::new(seed)),
+            final_store(&mut Random::new(seed))
+        );
+    }
+    let outcomes: std::collections::HashSet<_> = (0..8)
+        .map(|seed| final_store(&mut Random::new(seed)))
+        .collect();
+    assert_eq!(outcomes.len(), 2);
+}
+
+#[test]
+fn test_schedule_round_robin_rotates() {
+    let procs = Procs(
+        vec!["a", "b", "c"]
+            .into_iter()
+            .map(|s| {
+                (
+                    sym(s),
+                    fumola::ast::step::Proc::Spawn(fumola::ast::Exp::Hole),
+                )
+            })
+            .collect(),
+    );
+    let mut sched = RoundRobin::default();
+    let firsts: Vec<_> = (0..4).map(|_| sched.order(&procs)[0].clone()).collect();
+    assert_eq!(firsts, vec![sym("a"), sym("b"), sym("c"), sym("a")]);
+}
+
+#[test]
+fn test_schedule_policy_names() {
+    assert_eq!("round-robin".parse(), Ok(Policy::RoundRobin));
+    assert!("fifo".parse::<Policy>().is_err());
+}
@@ -0,0 +1,82 @@
+use fumola::parser::ExpParser;
+use fumola::scope::{check, report, Unbound, VarKind};
+
+fn unbound(input: &str) -> Vec<Unbound> {
+    check(&ExpParser::new().parse(input).unwrap())
+}
+
+fn names(input: &str) -> Vec<(VarKind, String)> {
+    unbound(input)
+        .into_iter()
+        .map(|u| (u.kind, u.name))
+        .collect()
+}
+
+#[test]
+fn test_scope_bound() {
+    assert!(names(
+        "let x = ret 1; let [$a => y] = ret [$a => x]; \\z => ret [$x => x; $y => y; $z => z]"
+    )
+    .is_empty());
+    assert!(names("box rec f { let box g = ret f; g }; f").is_empty());
+    assert!(names(
+        "box id3 {\\x => \\y => \\z => ret x}; box one {ret 1}; id3 `(one) `(one) `(one)"
+    )
+    .is_empty());
+    assert!(names("let x = ret 1; ~$p { #$n { $a := x } }").is_empty());
+}
+
+#[test]
+fn test_scope_unbound() {
+    let val = |x: &str| (VarKind::Val, x.to_string());
+    let bx = |x: &str| (VarKind::Box, x.to_string());
+    assert_eq!(names("ret x"), vec![val("x")]);
+    assert_eq!(names("f 1"), vec![bx("f")]);
+    // values are not boxes, and boxes are not values
+    assert_eq!(names("let f = ret 1; f"), vec![bx("f")]);
+    assert_eq!(names("box f {ret 1}; ret f"), vec![val("f")]);
+    // box code sees neither the values nor the boxes around it
+    assert_eq!(
+        names("let x = ret 1; box g {ret 2}; box f {let y = g; ret x}; f"),
+        vec![bx("g"), val("x")]
+    );
+    // a rec box names itself as a value, not as a box
+    assert_eq!(names("box rec f { f }; f"), vec![bx("f")]);
+    // call-by-value is hoisted out of the case that binds x
+    assert_eq!(
+        names("switch #$a(1) { #$a(x) { ret `(ret x) } }"),
+        vec![val("x")]
+    );
+}
+
+#[test]
+fn test_scope_report() {
+    let input = "let x = ret 1;\nret [$x => x; $y => y]";
+    assert_eq!(
+        report(input, &unbound(input)),
+        "2:1: unbound value variable y\n"
+    );
+}
+
+#[test]
+fn test_scope_run_exit_code() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fumola"))
+        .args(["run", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"~$p { ret y }")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with("1:7: unbound value variable y\n"));
+}
@@ -0,0 +1,127 @@
+use fumola::ast::{
+    step::{Error, Proc, PutPolicy, StorePolicy, System},
+    Sym, Val,
+};
+use fumola::check::initial_system;
+use fumola::incr::Engine;
+use fumola::schedule::ByName;
+use fumola::step::{fully, nest_path, Fuel};
+
+fn run(input: &str, policy: StorePolicy) -> System {
+    let mut sys = initial_system(input, policy).unwrap();
+    fully(&mut sys);
+    sys
+}
+
+fn policy(default: PutPolicy, nests: &[(&str, PutPolicy)]) -> StorePolicy {
+    let path = |s: &str| {
+        let nests: Vec<Sym> = s.split('/').map(|n| Sym::Id(n.to_string())).collect();
+        nest_path(nests.iter()).unwrap()
+    };
+    StorePolicy {
+        default,
+        nests: nests.iter().map(|(s, p)| (path(s), *p)).collect(),
+    }
+}
+
+fn error(sys: &System) -> Option<Error> {
+    match sys.procs.0.get(&Sym::None) {
+        Some(Proc::Error(_, e)) => Some(e.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_store_overwrite_traced() {
+    let sys = run("let _ = $a := 1; $a := 2", StorePolicy::default());
+    assert!(error(&sys).is_none());
+    assert_eq!(
+        format!("{}", sys.procs),
+        "[% => halted([put a <= 1; overwrite a: 1 => 2; put a <= 2])]"
+    );
+}
+
+#[test]
+fn test_store_write_once() {
+    let input = "let _ = $a := 1; let _ = $a := 1; $a := 2";
+    let sys = run(input, policy(PutPolicy::WriteOnce, &[]));
+    assert!(matches!(
+        error(&sys),
+        Some(Error::Overwrite(_, Val::Num(1), Val::Num(1)))
+    ));
+    let sys = run(input, policy(PutPolicy::OverwriteIfEqual, &[]));
+    assert!(matches!(
+        error(&sys),
+        Some(Error::Overwrite(_, Val::Num(1), Val::Num(2)))
+    ));
+    assert_eq!(format!("{}", sys.store), "[a => 1]");
+}
+
+#[test]
+fn test_store_nest_policy() {
+    let input = "#$n { let _ = $a := 1; let _ = #$m { let _ = $b := 1; $b := 2 }; $a := 2 }";
+    // the innermost nest with a policy decides
+    let p = policy(
+        PutPolicy::Overwrite,
+        &[("n", PutPolicy::WriteOnce), ("n/m", PutPolicy::Overwrite)],
+    );
+    let sys = run(input, p);
+    assert!(matches!(
+        error(&sys),
+        Some(Error::Overwrite(_, Val::Num(1), Val::Num(2)))
+    ));
+    assert_eq!(format!("{}", sys.store), "[n/a => 1; n/m/b => 2]");
+    let sys = run(
+        input,
+        policy(PutPolicy::WriteOnce, &[("n", PutPolicy::Overwrite)]),
+    );
+    assert!(error(&sys).is_none());
+}
+
+#[test]
+fn test_store_nest_policy_by_path() {
+    // two nests named m, under different parents
+    let input = "\
+let _ = #$a { #$m { let _ = $x := 1; $x := 2 } };
+#$b { #$m { let _ = $x := 1; $x := 2 } }";
+    let sys = run(
+        input,
+        policy(PutPolicy::Overwrite, &[("b/m", PutPolicy::WriteOnce)]),
+    );
+    assert!(matches!(
+        error(&sys),
+        Some(Error::Overwrite(Sym::Nest(b, _), _, _)) if *b == Sym::Id("b".to_string())
+    ));
+    assert_eq!(format!("{}", sys.store), "[a/m/x => 2; b/m/x => 1]");
+    // a bare name is the path of a nest at the top only
+    let sys = run(
+        input,
+        policy(PutPolicy::Overwrite, &[("m", PutPolicy::WriteOnce)]),
+    );
+    assert!(error(&sys).is_none());
+}
+
+#[test]
+fn test_store_policy_replayed_nests() {
+    let input = "\
+let box f = {\\x => #$n { let _ = $o := 1; ret x }};
+let a = f 1;
+f 1";
+    // the replayed put overwrites, as the first put of n/o did not
+    let mut sys = initial_system(input, StorePolicy::default()).unwrap();
+    let mut engine = Engine::new();
+    engine.run(&mut sys, &mut ByName, &Fuel::default());
+    assert_eq!(engine.reused, 1);
+    assert_eq!(
+        format!("{}", sys),
+        format!("{}", run(input, StorePolicy::default()))
+    );
+    assert!(format!("{}", sys.procs).contains("#n {overwrite n/o: 1 => 1; put n/o <= 1"));
+
+    // under write-once, the nest is not replayed, and fails when it runs again
+    let mut sys = initial_system(input, policy(PutPolicy::WriteOnce, &[])).unwrap();
+    let mut engine = Engine::new();
+    engine.run(&mut sys, &mut ByName, &Fuel::default());
+    assert_eq!(engine.reused, 0);
+    assert!(matches!(error(&sys), Some(Error::Overwrite(_, _, _))));
+}
@@ -0,0 +1,52 @@
+use fumola::ast::{step::Event, Sym};
+use fumola::check::system;
+
+#[test]
+fn test_timeline_steps() {
+    let sys = system("let p = ~$p { #$n { $a := 1 } }; &p").unwrap();
+    let stamps: Vec<_> = sys
+        .timeline
+        .0
+        .iter()
+        .map(|st| format!("{} {} {:?}", st.step, st.proc, st.event))
+        .collect();
+    assert_eq!(
+        stamps,
+        vec![
+            "2 % Spawn(Id(\"p\"))",
+            "3 p NestBegin(Id(\"n\"))",
+            "4 p Put(Nest(Id(\"n\"), Id(\"a\")), Num(1))",
+            "5 p NestEnd(Id(\"n\"))",
+            "6 p Halt(Ptr(Nest(Id(\"n\"), Id(\"a\"))))",
+            "7 % Link(Proc(Id(\"p\")), Ptr(Nest(Id(\"n\"), Id(\"a\"))))",
+            "8 % Halt(Ptr(Nest(Id(\"n\"), Id(\"a\"))))",
+        ]
+    );
+    assert!(matches!(
+        &sys.timeline.0.last().unwrap().event,
+        Event::Halt(_)
+    ));
+    assert_eq!(sys.timeline.0[0].proc, Sym::None);
+}
+
+#[test]
+fn test_chrome_export() {
+    let sys = system("let p = ~$p { #$n { $a := 1 } }; let x = &p; @x").unwrap();
+    let json = fumola::chrome::system(&sys);
+    let events = json["traceEvents"].as_array().unwrap();
+    let phases = |ph: &str| events.iter().filter(|e| e["ph"] == ph).count();
+    assert_eq!(phases("B"), 1);
+    assert_eq!(phases("E"), 1);
+    // spawn, put, halt (p), link, get, halt (%).
+    assert_eq!(phases("i"), 6);
+    // spawn -> p, halt p -> link, put -> get.
+    assert_eq!(phases("s"), 3);
+    assert_eq!(phases("f"), 3);
+    let names: Vec<_> = events
+        .iter()
+        .filter(|e| e["name"] == "thread_name")
+        .map(|e| e["args"]["name"].as_str().unwrap().to_string())
+        .collect();
+    // metadata follows the sorted track order
+    assert_eq!(names, vec!["%", "p"]);
+}
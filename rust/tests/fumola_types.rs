@@ -0,0 +1,148 @@
+use fumola::parser::ExpParser;
+use fumola::types::{check, exhaustiveness, exhaustiveness_report, report};
+
+fn ty(input: &str) -> String {
+    match check(&ExpParser::new().parse(input).unwrap()) {
+        Ok(t) => t.to_string(),
+        Err(es) => panic!("{}: {}", input, report(input, &es)),
+    }
+}
+
+fn errors(input: &str) -> String {
+    match check(&ExpParser::new().parse(input).unwrap()) {
+        Ok(t) => panic!("{}: unexpected type {}", input, t),
+        Err(es) => report(input, &es),
+    }
+}
+
+fn labels(input: &str) -> String {
+    let found = exhaustiveness(&ExpParser::new().parse(input).unwrap());
+    exhaustiveness_report(input, &found)
+}
+
+#[test]
+fn test_types_infer() {
+    assert_eq!(ty("let x = ret 1; ret x"), "ret num");
+    assert_eq!(ty("let x = #$n{ $a := 3 }; @x"), "ret num");
+    assert_eq!(ty("let p = ~$p { ret $s }; ret p"), "ret ~sym");
+    assert_eq!(ty("let p = ~$p { ret $s }; &p"), "ret sym");
+    assert_eq!(
+        ty("let [$secret => v] = ret [$secret => 42; $other => $x]; ret [$result => v]"),
+        "ret [$result => num]"
+    );
+    assert_eq!(
+        ty("{ $apple => ret 1; $banana => \\x => x := x } <= $banana $s"),
+        "ret !sym"
+    );
+    assert_eq!(
+        ty("switch #$apple(1) { #$apple(x){ret x}; #$banana(x){ret 2} }"),
+        "ret num"
+    );
+}
+
+#[test]
+fn test_types_box_schemes() {
+    // each use of a box gets its own instance of the box's type
+    assert_eq!(
+        ty("box id {\\x => ret x}; let a = id 1; let b = id $s; ret [$a => a; $b => b]"),
+        "ret [$a => num; $b => sym]"
+    );
+    assert_eq!(
+        ty("box id3 {\\x => \\y => \\z => ret x}; box one {ret 1}; id3 `(one) $b !c"),
+        "ret num"
+    );
+}
+
+#[test]
+fn test_types_reject() {
+    // NoStep: applying a returner, or returning a function
+    assert!(errors("box f {ret 1}; f 2").starts_with("1:16: expected num -> "));
+    assert!(errors("\\x => ret x").starts_with("expected ret "));
+    // NotASymbol and NotAPointer
+    assert_eq!(errors("1 := 2"), "1:1: expected sym, found num\n");
+    assert!(errors("@$s").ends_with(", found sym\n"));
+    assert!(errors("let p = ~$p { ret 42 }; let x = &p; @x").starts_with("1:37: expected !"));
+    // PatternError
+    assert!(errors("let [$a => x] = ret 1; ret x").ends_with(", found num\n"));
+    assert!(errors("let [$a => x] = ret [$b => 1]; ret x").ends_with(", found [$b => num]\n"));
+    // missing case
+    assert!(errors("switch #$c(1) { #$a(x) { ret x } }").starts_with("1:1: expected #[$a("));
+}
+
+#[test]
+fn test_types_run_exit_code() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let status = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fumola"))
+            .arg("run")
+            .args(args)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"box f {ret 1}; f 2")
+            .unwrap();
+        child.wait().unwrap().code()
+    };
+    assert_eq!(status(&["--types"]), Some(1));
+    assert_eq!(status(&[]), Some(3));
+}
+
+#[test]
+fn test_types_exhaustiveness() {
+    // labels flow from every call of a box, and through case payloads
+    assert_eq!(
+        labels(
+            "box f { \\x => switch x { #$a(n) { ret n }; #$b(n) { ret n }; #$a(m) { ret m } } };
+let y = f #$a(1);
+f #$c(2)"
+        ),
+        "1:15: switch is not exhaustive: no case for #$c; redundant cases #$b, #$a\n"
+    );
+    assert_eq!(
+        labels("let v = ret #$a(#$b(1)); switch v { #$a(w) { switch w { #$b(n) { ret n }; #$c(n) { ret n } } } }"),
+        "1:46: switch has redundant cases #$c\n"
+    );
+    assert_eq!(
+        labels("box b {{ $l => ret 1; $m => ret 2 }}; let x = b <= $l; b <= $n"),
+        "1:56: projection is not exhaustive: no branch for $n\n"
+    );
+}
+
+#[test]
+fn test_types_exhaustiveness_unknown() {
+    for input in [
+        // labels computed at runtime, or read from the store, are not judged
+        "let l = @!l; switch #l(1) { #$a(x) { ret x } }",
+        "let v = @!v; switch v { #$a(x) { ret x } }",
+        // nor are boxes that are never called
+        "box f { \\x => switch x { #$a(n) { ret n } } }; ret 1",
+    ] {
+        assert_eq!(labels(input), "", "{}", input);
+    }
+}
+
+#[test]
+fn test_types_check_opt_in() {
+    use std::process::Command;
+    // inference has no recursive types, so this is only rejected when asked
+    let status = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_fumola"))
+            .arg("check")
+            .args(args)
+            .arg("box rec z { ret z }; z")
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    assert_eq!(status(&[]), Some(0));
+    assert_eq!(status(&["--types"]), Some(1));
+}
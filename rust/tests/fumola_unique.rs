@@ -0,0 +1,69 @@
+use fumola::parser::ExpParser;
+use fumola::unique::{check, report};
+
+fn names(input: &str) -> String {
+    report(
+        input,
+        &check(&ExpParser::new().parse(input).unwrap()).unwrap(),
+    )
+}
+
+#[test]
+fn test_unique_distinct_names() {
+    for input in [
+        "let _ = $a := 1; let _ = #$n { $a := 2 }; ~$p { $b := 3 }",
+        "let box w = {\\x => x := 1}; let _ = #$n { w $a }; w $a",
+        "switch #$a(1) { #$a(x) { $c := x }; #$b(y) { $c := y } }",
+        "{ $l => $c := 1; $r => $c := 2 } <= $l",
+    ] {
+        assert_eq!(names(input), "", "{}", input);
+    }
+}
+
+#[test]
+fn test_unique_reused_names() {
+    assert_eq!(
+        names("let _ = $a := 1; $a := 2"),
+        "1:18: put a reuses put a at 1:9\n"
+    );
+    assert_eq!(
+        names("let _ = $a := 1; ~$a { ret 0 }"),
+        "1:18: spawn a reuses put a at 1:9\n"
+    );
+    assert_eq!(
+        names("let box w = {\\x => x := 1}; let _ = w $a; w $a"),
+        "1:20: put a runs again, and reuses a\n"
+    );
+    // a put of a symbol computed at runtime may reuse any name
+    assert_eq!(
+        names("let x = @!p; let _ = x := 1; $a := 2"),
+        "1:30: put a may reuse put * at 1:22\n"
+    );
+    // within a nest, it may reuse any name of that nest
+    assert_eq!(
+        names("let x = @!p; #$n { let _ = $a := 1; x := 2 }"),
+        "1:37: put n/* may reuse put n/a at 1:28\n"
+    );
+}
+
+#[test]
+fn test_unique_unknown_box() {
+    // a box read from the store puts x twice
+    assert_eq!(
+        names("let p = $b := {$x := 1}; let box f = @p; let _ = f; f"),
+        "1:50: put * may reuse put b at 1:9\n\
+         1:53: put * may reuse put b at 1:9\n\
+         1:50: box of unknown code may put any names, more than once\n\
+         1:53: put * may reuse put * at 1:50\n\
+         1:53: box of unknown code may put any names, more than once\n"
+    );
+}
+
+#[test]
+fn test_unique_recursive_names() {
+    assert_eq!(
+        names("box rec f { \\x => let _ = x := 1; let box g = ret f; g $b }; f $a"),
+        "1:27: put * runs again, and may reuse a\n\
+         1:27: put * may run more than once, in a recursive call\n"
+    );
+}
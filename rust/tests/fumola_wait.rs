@@ -0,0 +1,79 @@
+use fumola::ast::step::Proc;
+use fumola::ast::Sym;
+use fumola::check::system;
+use fumola::cover::strip;
+use fumola::format::source;
+use fumola::parser::ExpParser;
+use fumola::quiesce::{report, Cause};
+use fumola::race::detect;
+
+const PRODUCER_CONSUMER: &str = "\
+let c = ~$c { let x = @?!a; $b := x };
+let _ = $a := 5;
+let y = @?!b;
+ret y";
+
+#[test]
+fn test_wait_get_after_put() {
+    let sys = system(PRODUCER_CONSUMER).unwrap();
+    assert_eq!(
+        format!("{}", sys.procs),
+        "[% => halted([put a <= 5; wait b; get b => 5; ret 5]); \
+         c => halted([wait a; get a => 5; put b <= 5])]"
+    );
+    // each get is ordered after the put it waits for, without a link
+    assert!(detect(&sys).is_empty());
+    let events: Vec<String> = sys
+        .timeline
+        .0
+        .iter()
+        .map(|st| st.event.to_string())
+        .collect();
+    assert!(
+        events.contains(&"get-wait a => 5".to_string()),
+        "{:?}",
+        events
+    );
+    assert!(
+        !events.iter().any(|e| e.starts_with("link")),
+        "{:?}",
+        events
+    );
+    // a symbol already put is read without waiting
+    let sys = system("let _ = $a := 1; @?!a").unwrap();
+    assert_eq!(
+        format!("{}", sys.procs),
+        "[% => halted([put a <= 1; get a => 1])]"
+    );
+}
+
+#[test]
+fn test_wait_never_put() {
+    let sys = system("let x = @?!a; ret x").unwrap();
+    assert!(matches!(
+        sys.procs.0.get(&Sym::None),
+        Some(Proc::WaitingForPtr(_, _))
+    ));
+    let r = report(&sys);
+    assert_eq!(r.stuck.len(), 1);
+    assert!(matches!(&r.stuck[0].cause, Cause::NeverPut(s) if s == &Sym::Id("a".to_string())));
+    // unlike a blocking get, a get of an undefined symbol fails
+    let sys = system("let x = @!a; ret x").unwrap();
+    assert!(matches!(
+        sys.procs.0.get(&Sym::None),
+        Some(Proc::Error(_, _))
+    ));
+}
+
+#[test]
+fn test_wait_syntax() {
+    let e = ExpParser::new().parse("let x = @?!a; @?x").unwrap();
+    assert_eq!(format!("{}", strip(&e)), "let x = @?!a; @?x");
+    let src = source(&e);
+    assert_eq!(
+        strip(&ExpParser::new().parse(&src).unwrap()),
+        strip(&e),
+        "{}",
+        src
+    );
+}